@@ -1,21 +1,75 @@
 use derivative::Derivative;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
 use std::fs::File;
+use std::os::unix::process::ExitStatusExt;
 use std::path::{Path, PathBuf};
 use std::process::{ExitStatus, Output};
+use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::process::Command;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+const DEFAULT_INTERRUPT_GRACE: Duration = Duration::from_secs(60);
+
+type HostFilter = Box<dyn Fn(&str) -> bool + Send + Sync>;
 
 pub struct PulumiCLI {
   workdir: PathBuf,
+  interrupt_grace: Duration,
+  owns_lock_host: Option<HostFilter>,
+  interrupted: watch::Receiver<bool>,
+  signal_listener: JoinHandle<()>,
 }
 
 impl PulumiCLI {
+  /// Creates the CLI and starts listening for SIGTERM and SIGINT, which
+  /// interrupt the running command and skip all following ones.
   pub fn new(workdir: impl AsRef<Path>) -> Self {
+    let mut terminate = signal(SignalKind::terminate()).unwrap();
+    let mut interrupt = signal(SignalKind::interrupt()).unwrap();
+    let (sender, interrupted) = watch::channel(false);
+    let signal_listener = tokio::spawn(async move {
+      tokio::select! {
+        _ = terminate.recv() => log::warn!("received SIGTERM"),
+        _ = interrupt.recv() => log::warn!("received SIGINT"),
+      }
+      let _ = sender.send(true);
+    });
+
     PulumiCLI {
       workdir: workdir.as_ref().to_path_buf(),
+      interrupt_grace: DEFAULT_INTERRUPT_GRACE,
+      owns_lock_host: None,
+      interrupted,
+      signal_listener,
     }
   }
 
+  /// How long an interrupted command gets to stop before it is killed.
+  pub fn with_interrupt_grace(mut self, interrupt_grace: Duration) -> Self {
+    self.interrupt_grace = interrupt_grace;
+    self
+  }
+
+  /// Cancels the operation holding the stack lock when a command fails on it
+  /// and retries the command once. Only locks whose every holder was created
+  /// on a host accepted by `owns_host` are cancelled, so callers can limit
+  /// this to hosts they know are gone.
+  pub fn with_stale_lock_recovery(
+    mut self,
+    owns_host: impl Fn(&str) -> bool + Send + Sync + 'static,
+  ) -> Self {
+    self.owns_lock_host = Some(Box::new(owns_host));
+    self
+  }
+
+  pub fn interrupted(&self) -> bool {
+    *self.interrupted.borrow()
+  }
+
   pub async fn login(&self, options: LoginOptions) -> ExitStatus {
     let mut command = Command::new("pulumi");
     command.arg("login").arg(options.url);
@@ -31,13 +85,16 @@ impl PulumiCLI {
       options.stack
     };
     command.arg("stack").arg("init").arg(combined_stack);
+    if let Some(secrets_provider) = options.secrets_provider {
+      command.arg("--secrets-provider").arg(secrets_provider);
+    }
 
     self.spawn(command).await
   }
 
   pub async fn cancel(&self, options: CancelOptions) -> ExitStatus {
     let mut command = Command::new("pulumi");
-    command.arg("cancel");
+    command.arg("cancel").arg("--yes");
     if let Some(stack) = options.stack {
       command.arg(stack);
     }
@@ -45,6 +102,12 @@ impl PulumiCLI {
   }
 
   pub async fn up(&self, options: UpOptions) -> ExitStatus {
+    self
+      .spawn_locking(options.stack.clone(), || Self::up_command(&options))
+      .await
+  }
+
+  fn up_command(options: &UpOptions) -> Command {
     let mut command = Command::new("pulumi");
     command.arg("up");
 
@@ -97,10 +160,97 @@ impl PulumiCLI {
       command.arg("--show-sames");
     }
 
-    self.spawn(command).await
+    command
+  }
+
+  pub async fn stack_export(&self, options: StackExportOptions) -> Output {
+    let mut command = Command::new("pulumi");
+    command.arg("stack").arg("export");
+
+    if let Some(stack) = &options.stack {
+      command.arg("--stack").arg(stack);
+    }
+
+    command.current_dir(&self.workdir);
+    command.output().await.unwrap()
   }
 
-  pub async fn spawn(&self, mut command: Command) -> ExitStatus {
+  pub async fn change_secrets_provider(
+    &self,
+    options: ChangeSecretsProviderOptions,
+  ) -> ExitStatus {
+    let command = || {
+      let mut command = Command::new("pulumi");
+      command
+        .arg("stack")
+        .arg("change-secrets-provider")
+        .arg(&options.secrets_provider);
+
+      if let Some(stack) = &options.stack {
+        command.arg("--stack").arg(stack);
+      }
+      command
+    };
+
+    self.spawn_locking(options.stack.clone(), command).await
+  }
+
+  pub async fn stack_output(&self, options: StackOutputOptions) -> Output {
+    let mut command = Command::new("pulumi");
+    command.arg("stack").arg("output").arg("--json");
+
+    if let Some(stack) = &options.stack {
+      command.arg("--stack").arg(stack);
+    }
+    if options.show_secrets {
+      command.arg("--show-secrets");
+    }
+
+    command.current_dir(&self.workdir);
+    command.output().await.unwrap()
+  }
+
+  pub async fn spawn(&self, command: Command) -> ExitStatus {
+    self.spawn_captured(command).await.0
+  }
+
+  /// Spawns a command that takes the stack lock, see
+  /// `with_stale_lock_recovery`.
+  async fn spawn_locking(
+    &self,
+    stack: Option<String>,
+    command: impl Fn() -> Command,
+  ) -> ExitStatus {
+    let Some(owns_host) = &self.owns_lock_host else {
+      return self.spawn(command()).await;
+    };
+
+    let (status, output) = self.spawn_captured(command()).await;
+    if status.success() || !is_locked(&output) {
+      return status;
+    }
+    let hosts = lock_hosts(&output);
+    if hosts.is_empty() || !hosts.iter().all(|host| owns_host(host)) {
+      log::warn!("stack is locked by {:?}, not cancelling", hosts);
+      return status;
+    }
+
+    log::warn!("stack is locked by {:?}, cancelling and retrying", hosts);
+    self.cancel(CancelOptions { stack }).await;
+    self.spawn(command()).await
+  }
+
+  /// Spawns the command, logging and collecting its output. Interruptions
+  /// are forwarded as SIGINT so pulumi can checkpoint and stop gracefully.
+  pub async fn spawn_captured(
+    &self,
+    mut command: Command,
+  ) -> (ExitStatus, String) {
+    if self.interrupted() {
+      log::warn!("interrupted, not running {:?}", command.as_std());
+      return (ExitStatus::from_raw(Signal::SIGINT as i32), String::new());
+    }
+
     command.stdout(std::process::Stdio::piped());
     command.stderr(std::process::Stdio::piped());
     command.current_dir(&self.workdir);
@@ -114,39 +264,81 @@ impl PulumiCLI {
     let stderr_reader = BufReader::new(stderr);
 
     let stdout_handle = tokio::spawn(async move {
+      let mut output = String::new();
       let mut lines = stdout_reader.lines();
       while let Some(line) = lines.next_line().await.unwrap() {
         log::info!("{}", line);
+        output.push_str(&line);
+        output.push('\n');
       }
+      output
     });
 
     let stderr_handle = tokio::spawn(async move {
+      let mut output = String::new();
       let mut lines = stderr_reader.lines();
       while let Some(line) = lines.next_line().await.unwrap() {
         log::error!("{}", line);
+        output.push_str(&line);
+        output.push('\n');
       }
+      output
     });
 
-    tokio::try_join!(stdout_handle, stderr_handle).unwrap();
+    let mut interrupted = self.interrupted.clone();
+    let status = tokio::select! {
+      status = child.wait() => status.unwrap(),
+      // the borrowed value holds a lock, drop it before awaiting the child
+      Ok(()) = async {
+        interrupted.wait_for(|interrupted| *interrupted).await.map(|_| ())
+      } => {
+        if let Some(id) = child.id() {
+          let _ = kill(Pid::from_raw(id as i32), Signal::SIGINT);
+        }
+        match tokio::time::timeout(self.interrupt_grace, child.wait()).await {
+          Ok(status) => status.unwrap(),
+          Err(_) => {
+            log::error!(
+              "command did not stop within {:?}, killing it",
+              self.interrupt_grace
+            );
+            child.kill().await.unwrap();
+            child.wait().await.unwrap()
+          }
+        }
+      }
+    };
+
+    let (stdout, stderr) =
+      tokio::try_join!(stdout_handle, stderr_handle).unwrap();
 
-    child.wait().await.unwrap()
+    (status, stdout + &stderr)
   }
 
   pub async fn destroy(&self, options: DestroyOptions) -> ExitStatus {
-    let mut command = Command::new("pulumi");
-    command.arg("destroy");
+    let command = || {
+      let mut command = Command::new("pulumi");
+      command.arg("destroy");
 
-    if let Some(stack) = &options.stack {
-      command.arg("--stack").arg(stack);
-    }
-    if options.yes {
-      command.arg("--yes");
-    }
-    if options.skip_preview {
-      command.arg("--skip-preview");
-    }
+      if let Some(stack) = &options.stack {
+        command.arg("--stack").arg(stack);
+      }
+      if options.yes {
+        command.arg("--yes");
+      }
+      if options.skip_preview {
+        command.arg("--skip-preview");
+      }
+      command
+    };
 
-    self.spawn(command).await
+    self.spawn_locking(options.stack.clone(), command).await
+  }
+}
+
+impl Drop for PulumiCLI {
+  fn drop(&mut self) {
+    self.signal_listener.abort();
   }
 }
 
@@ -172,12 +364,46 @@ pub struct UpOptions {
   pub show_sames: bool,
 }
 
+/// Whether a failed operation was rejected because the stack is locked by
+/// another, possibly interrupted, operation.
+pub fn is_locked(output: &str) -> bool {
+  output.contains("the stack is currently locked")
+    || output.contains("Another update is currently in progress")
+}
+
+/// The hosts of the lock holders pulumi lists in a locked error, as in
+/// `<lock file>: created by <user>@<host> (pid <pid>) at <time>`. The cloud
+/// backend does not list holders.
+pub fn lock_hosts(output: &str) -> Vec<&str> {
+  output
+    .lines()
+    .filter_map(|line| line.split_once("created by ")?.1.split(' ').next())
+    .filter_map(|holder| holder.rsplit_once('@'))
+    .map(|(_, host)| host)
+    .collect()
+}
+
 pub struct CancelOptions {
   pub stack: Option<String>,
 }
 pub struct StackInitOptions {
   pub stack: String,
   pub organization: Option<String>,
+  pub secrets_provider: Option<String>,
+}
+
+pub struct StackExportOptions {
+  pub stack: Option<String>,
+}
+
+pub struct ChangeSecretsProviderOptions {
+  pub stack: Option<String>,
+  pub secrets_provider: String,
+}
+
+pub struct StackOutputOptions {
+  pub stack: Option<String>,
+  pub show_secrets: bool,
 }
 
 pub struct DestroyOptions {
@@ -189,3 +415,30 @@ pub struct DestroyOptions {
 pub struct LoginOptions {
   pub url: String,
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn lock_hosts_lists_every_holder() {
+    let output = "error: the stack is currently locked by 2 lock(s). Either \
+      wait for the other process(es) to end or delete the lock file with \
+      `pulumi cancel`.\n  \
+      s3://state/.pulumi/locks/organization/app/dev/1.json: created by \
+      root@app-28924020-x7k2p (pid 12) at 2024-01-01T00:00:00Z\n  \
+      s3://state/.pulumi/locks/organization/app/dev/2.json: created by \
+      jane@laptop (pid 99) at 2024-01-01T00:01:00Z\n";
+
+    assert_eq!(lock_hosts(output), vec!["app-28924020-x7k2p", "laptop"]);
+  }
+
+  #[test]
+  fn lock_hosts_is_empty_without_holders() {
+    let output = "error: [409] Conflict: Another update is currently in \
+      progress.\n";
+
+    assert!(is_locked(output));
+    assert!(lock_hosts(output).is_empty());
+  }
+}
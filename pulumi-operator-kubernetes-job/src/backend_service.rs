@@ -0,0 +1,112 @@
+use k8s_openapi::api::core::v1::Secret;
+use pulumi_operator_kubernetes::kubernetes::service::KubernetesService;
+use pulumi_operator_kubernetes::stack::auth::inner::{
+  BackendEnvVar, InnerStackAuthSpec,
+};
+use pulumi_operator_kubernetes::Inst;
+use springtime_di::Component;
+use thiserror::Error;
+
+#[derive(Component)]
+pub struct BackendService {
+  kubernetes_service: Inst<KubernetesService>,
+}
+
+#[derive(Debug, Error)]
+pub enum BackendError {
+  #[error("Kubernetes error: {0}")]
+  Kubernetes(#[from] kube::Error),
+
+  #[error("Cannot infer backend type from {0}, set backendType or backendEnv")]
+  UnknownBackendType(String),
+
+  #[error("Secret {0} doesn't contain key {1}")]
+  KeyMissing(String, String),
+
+  #[error("Secret {0} key {1} is not valid UTF-8")]
+  Utf8(String, String),
+}
+
+const ACCESS_TOKEN_KEY: &str = "token";
+
+impl BackendService {
+  /// Reads the token from the access token secret, if there is one.
+  pub async fn access_token(
+    &self,
+    namespace: &str,
+    auth: &InnerStackAuthSpec,
+  ) -> Result<Option<String>, BackendError> {
+    let Some(secret_name) = &auth.access_token_secret else {
+      return Ok(None);
+    };
+
+    let data = self
+      .kubernetes_service
+      .get_in_namespace::<Secret>(namespace, secret_name)
+      .await?
+      .data
+      .unwrap_or_default();
+    let value = data.get(ACCESS_TOKEN_KEY).ok_or_else(|| {
+      BackendError::KeyMissing(secret_name.clone(), ACCESS_TOKEN_KEY.into())
+    })?;
+
+    String::from_utf8(value.0.clone()).map(Some).map_err(|_| {
+      BackendError::Utf8(secret_name.clone(), ACCESS_TOKEN_KEY.into())
+    })
+  }
+
+  /// Exports the backend credentials from the auth secret as env vars.
+  pub async fn configure(
+    &self,
+    namespace: &str,
+    auth: &InnerStackAuthSpec,
+  ) -> Result<(), BackendError> {
+    let Some(secret_name) = &auth.backend_auth_secret else {
+      return Ok(());
+    };
+
+    let mapping = match (&auth.backend_env, auth.backend_type()) {
+      (Some(mapping), _) => mapping.clone(),
+      (None, Some(backend_type)) => backend_type
+        .default_env()
+        .iter()
+        .map(|name| BackendEnvVar {
+          name: name.to_string(),
+          key: None,
+          optional: None,
+        })
+        .collect(),
+      (None, None) => {
+        return Err(BackendError::UnknownBackendType(auth.backend.clone()))
+      }
+    };
+
+    let data = self
+      .kubernetes_service
+      .get_in_namespace::<Secret>(namespace, secret_name)
+      .await?
+      .data
+      .unwrap_or_default();
+
+    // validate every key before touching the environment
+    let mut env = Vec::with_capacity(mapping.len());
+    for var in mapping {
+      let key = var.key.unwrap_or_else(|| var.name.clone());
+      match data.get(&key) {
+        Some(value) => {
+          let value = String::from_utf8(value.0.clone())
+            .map_err(|_| BackendError::Utf8(secret_name.clone(), key))?;
+          env.push((var.name, value));
+        }
+        None if var.optional.unwrap_or(false) => {}
+        None => return Err(BackendError::KeyMissing(secret_name.clone(), key)),
+      }
+    }
+
+    for (name, value) in env {
+      std::env::set_var(name, value);
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,277 @@
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use fs2::FileExt;
+use pulumi_operator_kubernetes::config_provider::ConfigProvider;
+use pulumi_operator_kubernetes::Inst;
+use sha2::{Digest, Sha256};
+use springtime_di::Component;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+const LOCK_FILE: &str = ".lock";
+
+const GIT_DIR: &str = "git";
+// shared by every job, evicted as a whole like a single git mirror
+const PACKAGE_DIRS: [&str; 5] = ["plugins", "npm", "yarn", "pnpm", "pip"];
+
+#[derive(Component)]
+pub struct CacheService {
+  config_provider: Inst<ConfigProvider>,
+  #[component(default)]
+  lock: Mutex<Option<File>>,
+}
+
+#[derive(Debug, Error)]
+pub enum CacheError {
+  #[error("Cache IO error on {0}: {1}")]
+  Io(PathBuf, std::io::Error),
+
+  #[error("Invalid cache size {0}")]
+  InvalidSize(String),
+}
+
+/// A bare mirror of a git repository, locked for as long as it is alive.
+pub struct GitMirror {
+  pub path: PathBuf,
+  _lock: File,
+}
+
+impl CacheService {
+  /// Takes a shared lock on the cache for the lifetime of the job and points
+  /// the pulumi plugin directory and package manager caches into it.
+  pub fn setup(&self) -> Result<(), CacheError> {
+    let Some(root) = self.config_provider.cache_dir() else {
+      return Ok(());
+    };
+
+    for dir in PACKAGE_DIRS.into_iter().chain([GIT_DIR]) {
+      create_dir(&root.join(dir))?;
+    }
+
+    let lock = open_lock(&root.join(LOCK_FILE))?;
+    lock
+      .lock_shared()
+      .map_err(|err| CacheError::Io(root.join(LOCK_FILE), err))?;
+    *self.lock.lock().unwrap() = Some(lock);
+
+    let pulumi_home = match std::env::var_os("PULUMI_HOME") {
+      Some(pulumi_home) => PathBuf::from(pulumi_home),
+      None => PathBuf::from(std::env::var_os("HOME").unwrap_or_default())
+        .join(".pulumi"),
+    };
+    let plugins = pulumi_home.join("plugins");
+    if plugins.symlink_metadata().is_err() {
+      create_dir(&pulumi_home)?;
+      std::os::unix::fs::symlink(root.join("plugins"), &plugins)
+        .map_err(|err| CacheError::Io(plugins.clone(), err))?;
+    } else if !plugins.is_symlink() {
+      tracing::warn!(
+        "{} already exists, not caching plugins",
+        plugins.display()
+      );
+    }
+    touch(&root.join("plugins"));
+
+    std::env::set_var("npm_config_cache", root.join("npm"));
+    std::env::set_var("YARN_CACHE_FOLDER", root.join("yarn"));
+    std::env::set_var("npm_config_store_dir", root.join("pnpm"));
+    std::env::set_var("PIP_CACHE_DIR", root.join("pip"));
+    for dir in ["npm", "yarn", "pnpm", "pip"] {
+      touch(&root.join(dir));
+    }
+
+    Ok(())
+  }
+
+  /// Returns the mirror for the repository, exclusively locked so concurrent
+  /// jobs don't update it at the same time.
+  pub async fn git_mirror(
+    &self,
+    repository: &str,
+  ) -> Result<Option<GitMirror>, CacheError> {
+    let Some(root) = self.config_provider.cache_dir() else {
+      return Ok(None);
+    };
+
+    let name = hex::encode(&Sha256::digest(repository.as_bytes())[..16]);
+    let path = root.join(GIT_DIR).join(&name);
+    let lock_path = root.join(GIT_DIR).join(format!("{}.lock", name));
+
+    // waiting for another job must not block the runtime
+    let lock = open_lock(&lock_path)?;
+    let lock =
+      tokio::task::spawn_blocking(move || lock.lock_exclusive().map(|()| lock))
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|result| result)
+        .map_err(|err| CacheError::Io(lock_path, err))?;
+    create_dir(&path)?;
+    touch(&path);
+
+    Ok(Some(GitMirror { path, _lock: lock }))
+  }
+
+  /// Removes the least recently used entries until the cache fits into its
+  /// maximum size. Skipped while other jobs are using the cache.
+  pub fn evict(&self) -> Result<(), CacheError> {
+    let (Some(root), Some(max_size)) = (
+      self.config_provider.cache_dir(),
+      self.config_provider.cache_max_size(),
+    ) else {
+      return Ok(());
+    };
+    let max_size =
+      parse_quantity(&max_size).ok_or(CacheError::InvalidSize(max_size))?;
+
+    let mut guard = self.lock.lock().unwrap();
+    // release our shared lock first, flock doesn't upgrade atomically
+    guard.take();
+    let lock = open_lock(&root.join(LOCK_FILE))?;
+    if lock.try_lock_exclusive().is_err() {
+      tracing::info!("cache is in use, skipping eviction");
+      return Ok(());
+    }
+
+    // ranked by the same entries that are touched when they are used
+    let mut paths: Vec<PathBuf> =
+      PACKAGE_DIRS.iter().map(|dir| root.join(dir)).collect();
+    if let Ok(read_dir) = fs::read_dir(root.join(GIT_DIR)) {
+      // mirror locks are cleaned up together with their mirror
+      paths.extend(
+        read_dir
+          .flatten()
+          .map(|entry| entry.path())
+          .filter(|path| path.extension().is_none_or(|ext| ext != "lock")),
+      );
+    }
+    let mut entries = Vec::new();
+    for path in paths {
+      let Ok(metadata) = path.metadata() else {
+        continue;
+      };
+      let used = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+      entries.push((used, size_of(&path), path));
+    }
+
+    let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+    entries.sort_by_key(|(used, _, _)| *used);
+
+    for (_, size, path) in entries {
+      if total <= max_size {
+        break;
+      }
+      tracing::info!("evicting {} from cache", path.display());
+      let result = if path.is_dir() {
+        fs::remove_dir_all(&path)
+      } else {
+        fs::remove_file(&path)
+      };
+      match result {
+        Ok(()) => {
+          let mut lock_path = path.into_os_string();
+          lock_path.push(".lock");
+          let _ = fs::remove_file(lock_path);
+          total = total.saturating_sub(size);
+        }
+        Err(err) => {
+          tracing::warn!("failed to evict {}: {}", path.display(), err)
+        }
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn create_dir(path: &Path) -> Result<(), CacheError> {
+  fs::create_dir_all(path).map_err(|err| CacheError::Io(path.into(), err))
+}
+
+fn open_lock(path: &Path) -> Result<File, CacheError> {
+  OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .write(true)
+    .open(path)
+    .map_err(|err| CacheError::Io(path.into(), err))
+}
+
+// marks an entry as recently used for eviction
+fn touch(path: &Path) {
+  if let Err(err) =
+    File::open(path).and_then(|file| file.set_modified(SystemTime::now()))
+  {
+    tracing::warn!("failed to touch {}: {}", path.display(), err);
+  }
+}
+
+fn size_of(path: &Path) -> u64 {
+  WalkDir::new(path)
+    .into_iter()
+    .flatten()
+    .filter_map(|entry| entry.metadata().ok())
+    .filter(|metadata| metadata.is_file())
+    .map(|metadata| metadata.len())
+    .sum()
+}
+
+/// Parses a Kubernetes quantity like 10Gi or 500M into bytes.
+fn parse_quantity(quantity: &str) -> Option<u64> {
+  let quantity = quantity.trim();
+  let split = quantity
+    .find(|c: char| c.is_ascii_alphabetic())
+    .unwrap_or(quantity.len());
+  let (number, suffix) = quantity.split_at(split);
+
+  let multiplier: u64 = match suffix {
+    "" => 1,
+    "k" => 1000,
+    "M" => 1000u64.pow(2),
+    "G" => 1000u64.pow(3),
+    "T" => 1000u64.pow(4),
+    "P" => 1000u64.pow(5),
+    "Ki" => 1 << 10,
+    "Mi" => 1 << 20,
+    "Gi" => 1 << 30,
+    "Ti" => 1 << 40,
+    "Pi" => 1 << 50,
+    _ => return None,
+  };
+
+  let number: f64 = number.parse().ok()?;
+  Some((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn plain_numbers_are_bytes() {
+    assert_eq!(parse_quantity("1024"), Some(1024));
+    assert_eq!(parse_quantity(" 512 "), Some(512));
+  }
+
+  #[test]
+  fn binary_suffixes_are_powers_of_two() {
+    assert_eq!(parse_quantity("1Ki"), Some(1024));
+    assert_eq!(parse_quantity("500Mi"), Some(500 * 1024 * 1024));
+    assert_eq!(parse_quantity("1.5Gi"), Some(3 << 29));
+  }
+
+  #[test]
+  fn decimal_suffixes_are_powers_of_ten() {
+    assert_eq!(parse_quantity("2k"), Some(2_000));
+    assert_eq!(parse_quantity("10G"), Some(10_000_000_000));
+  }
+
+  #[test]
+  fn unknown_quantities_are_rejected() {
+    assert_eq!(parse_quantity("1KB"), None);
+    assert_eq!(parse_quantity("Gi"), None);
+    assert_eq!(parse_quantity("lots"), None);
+  }
+}
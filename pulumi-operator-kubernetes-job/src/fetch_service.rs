@@ -1,6 +1,6 @@
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 
-use kube::core::ObjectMeta;
+use pulumi_operator_kubernetes::stack::crd::PulumiStack;
 use pulumi_operator_kubernetes::stack::source::Source;
 use pulumi_operator_kubernetes::Inst;
 use springtime_di::Component;
@@ -8,6 +8,8 @@ use thiserror::Error;
 
 use crate::{
   git::service::{GitError, GitService},
+  http::service::{HttpError, HttpService},
+  inline::service::{InlineError, InlineService},
   oci::service::{OciError, OciService},
 };
 
@@ -15,6 +17,8 @@ use crate::{
 pub struct FetchService {
   git_service: Inst<GitService>,
   oci_service: Inst<OciService>,
+  http_service: Inst<HttpService>,
+  inline_service: Inst<InlineService>,
 }
 
 #[derive(Debug, Error)]
@@ -23,23 +27,48 @@ pub enum FetchError {
   Git(#[from] GitError),
   #[error("Failed to setup from oci image: {0}")]
   Oci(#[from] OciError),
+  #[error("Failed to setup from http archive: {0}")]
+  Http(#[from] HttpError),
+  #[error("Failed to setup inline program: {0}")]
+  Inline(#[from] InlineError),
+}
+
+#[derive(Debug)]
+pub struct FetchedSource {
+  pub path: PathBuf,
+  pub revision: Option<String>,
+  /// false if nothing the stack watches changed since the last deployment
+  pub changed: bool,
 }
 
 impl FetchService {
   pub async fn fetch(
     &self,
     source: &Source,
-    metadata: &ObjectMeta,
-  ) -> Result<PathBuf, FetchError> {
-    let pulumi_dir = match source {
+    pulumi_stack: &PulumiStack,
+  ) -> Result<FetchedSource, FetchError> {
+    let metadata = &pulumi_stack.metadata;
+    let fetched = match source {
       Source::Git(git_source) => {
-        self.git_service.fetch(git_source, metadata).await?.into()
-      }
-      Source::Oci(oci_source) => {
-        self.oci_service.fetch(oci_source, metadata).await?.into()
+        self.git_service.fetch(git_source, pulumi_stack).await?
       }
+      Source::Oci(oci_source) => FetchedSource {
+        path: self.oci_service.fetch(oci_source, metadata).await?.into(),
+        revision: None,
+        changed: true,
+      },
+      Source::Http(http_source) => FetchedSource {
+        path: self.http_service.fetch(http_source, metadata).await?.into(),
+        revision: None,
+        changed: true,
+      },
+      Source::Inline(program) => FetchedSource {
+        path: self.inline_service.fetch(program, metadata).await?.into(),
+        revision: None,
+        changed: true,
+      },
     };
 
-    Ok(pulumi_dir)
+    Ok(fetched)
   }
 }
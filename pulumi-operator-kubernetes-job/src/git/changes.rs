@@ -0,0 +1,185 @@
+use git2::{Oid, Repository};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+
+use crate::git::service::GitError;
+
+pub struct ChangeFilter {
+  globs: Option<GlobSet>,
+}
+
+impl ChangeFilter {
+  pub fn new(
+    path: Option<&str>,
+    watch_paths: &[String],
+  ) -> Result<Self, GitError> {
+    let path = path
+      .map(|path| path.trim_start_matches("./").trim_end_matches('/'))
+      .filter(|path| !path.is_empty() && *path != ".");
+
+    // without a path the whole repository belongs to the stack
+    let Some(path) = path else {
+      return Ok(Self { globs: None });
+    };
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in
+      std::iter::once(format!("{}/**", path)).chain(watch_paths.iter().cloned())
+    {
+      let glob = GlobBuilder::new(pattern.trim_start_matches("./"))
+        .literal_separator(true)
+        .build()
+        .map_err(|err| GitError::WatchPath(pattern.clone(), err))?;
+      builder.add(glob);
+    }
+    let globs = builder
+      .build()
+      .map_err(|err| GitError::WatchPath(path.to_string(), err))?;
+
+    Ok(Self { globs: Some(globs) })
+  }
+
+  pub fn is_active(&self) -> bool {
+    self.globs.is_some()
+  }
+
+  pub fn changed(
+    &self,
+    repo: &Repository,
+    previous: Oid,
+    current: Oid,
+  ) -> Result<bool, GitError> {
+    let Some(globs) = &self.globs else {
+      return Ok(true);
+    };
+
+    let old_tree = repo.find_commit(previous)?.tree()?;
+    let new_tree = repo.find_commit(current)?.tree()?;
+    let diff =
+      repo.diff_tree_to_tree(Some(&old_tree), Some(&new_tree), None)?;
+
+    let changed = diff.deltas().any(|delta| {
+      [delta.old_file().path(), delta.new_file().path()]
+        .into_iter()
+        .flatten()
+        .any(|path| globs.is_match(path))
+    });
+    Ok(changed)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use git2::Signature;
+
+  use super::*;
+
+  struct TestRepo {
+    dir: PathBuf,
+    repo: Repository,
+  }
+
+  impl TestRepo {
+    fn new(name: &str) -> Self {
+      let dir = std::env::temp_dir().join(format!(
+        "change-filter-{}-{}",
+        name,
+        std::process::id()
+      ));
+      let _ = std::fs::remove_dir_all(&dir);
+      let repo = Repository::init(&dir).unwrap();
+      Self { dir, repo }
+    }
+
+    fn commit(&self, file: &str) -> Oid {
+      let path = self.dir.join(file);
+      std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+      let content = std::fs::read_to_string(&path).unwrap_or_default();
+      std::fs::write(&path, content + "change\n").unwrap();
+
+      let mut index = self.repo.index().unwrap();
+      index.add_path(file.as_ref()).unwrap();
+      index.write().unwrap();
+      let tree = self.repo.find_tree(index.write_tree().unwrap()).unwrap();
+      let signature = Signature::now("test", "test@example.com").unwrap();
+      let parent = self
+        .repo
+        .head()
+        .ok()
+        .map(|head| head.peel_to_commit().unwrap());
+      self
+        .repo
+        .commit(
+          Some("HEAD"),
+          &signature,
+          &signature,
+          file,
+          &tree,
+          &parent.iter().collect::<Vec<_>>(),
+        )
+        .unwrap()
+    }
+  }
+
+  impl Drop for TestRepo {
+    fn drop(&mut self) {
+      let _ = std::fs::remove_dir_all(&self.dir);
+    }
+  }
+
+  fn changed(filter: &ChangeFilter, repo: &TestRepo, file: &str) -> bool {
+    let previous = repo.commit("initial");
+    let current = repo.commit(file);
+    filter.changed(&repo.repo, previous, current).unwrap()
+  }
+
+  #[test]
+  fn without_path_every_change_counts() {
+    let repo = TestRepo::new("without-path");
+    for path in [None, Some("."), Some("./")] {
+      let filter = ChangeFilter::new(path, &[]).unwrap();
+      assert!(!filter.is_active());
+      assert!(changed(&filter, &repo, "docs/README.md"));
+    }
+  }
+
+  #[test]
+  fn changes_below_the_path_count() {
+    let repo = TestRepo::new("below-path");
+    let filter = ChangeFilter::new(Some("./infra/"), &[]).unwrap();
+    assert!(filter.is_active());
+    assert!(changed(&filter, &repo, "infra/index.ts"));
+    assert!(changed(&filter, &repo, "infra/nested/stack.ts"));
+  }
+
+  #[test]
+  fn changes_outside_the_path_are_skipped() {
+    let repo = TestRepo::new("outside-path");
+    let filter = ChangeFilter::new(Some("infra"), &[]).unwrap();
+    assert!(!changed(&filter, &repo, "docs/README.md"));
+    assert!(!changed(&filter, &repo, "infrastructure/index.ts"));
+  }
+
+  #[test]
+  fn changes_in_watch_paths_count() {
+    let repo = TestRepo::new("watch-paths");
+    let filter = ChangeFilter::new(
+      Some("infra"),
+      &["./shared/**".to_string(), "*.json".to_string()],
+    )
+    .unwrap();
+    assert!(changed(&filter, &repo, "shared/lib/util.ts"));
+    assert!(changed(&filter, &repo, "package.json"));
+    // the separator is literal, top level patterns don't match nested files
+    assert!(!changed(&filter, &repo, "docs/config.json"));
+  }
+
+  #[test]
+  fn invalid_watch_paths_are_rejected() {
+    let result = ChangeFilter::new(Some("infra"), &["shared/[".to_string()]);
+    assert!(
+      matches!(result, Err(GitError::WatchPath(pattern, _)) if pattern == "shared/[")
+    );
+  }
+}
@@ -0,0 +1,190 @@
+use base64::engine::general_purpose::{STANDARD, STANDARD_NO_PAD};
+use base64::Engine;
+use git2::cert::CertHostkey;
+
+struct KnownHost {
+  patterns: Vec<String>,
+  key_type: String,
+  key: Vec<u8>,
+}
+
+pub struct HostKeyVerifier {
+  known_hosts: Vec<KnownHost>,
+  fingerprints: Vec<String>,
+}
+
+impl HostKeyVerifier {
+  pub fn new(known_hosts: Option<&str>, fingerprints: Option<&str>) -> Self {
+    let known_hosts = known_hosts
+      .map(|known_hosts| known_hosts.lines().filter_map(parse_line).collect())
+      .unwrap_or_default();
+
+    let fingerprints = fingerprints
+      .map(|fingerprints| {
+        fingerprints
+          .split_whitespace()
+          .map(|fingerprint| {
+            fingerprint
+              .trim_start_matches("SHA256:")
+              .trim_end_matches('=')
+              .to_string()
+          })
+          .collect()
+      })
+      .unwrap_or_default();
+
+    Self {
+      known_hosts,
+      fingerprints,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.known_hosts.is_empty() && self.fingerprints.is_empty()
+  }
+
+  pub fn verify(&self, hostname: &str, hostkey: &CertHostkey<'_>) -> bool {
+    if let Some(hash) = hostkey.hash_sha256() {
+      let fingerprint = STANDARD_NO_PAD.encode(hash);
+      if self.fingerprints.contains(&fingerprint) {
+        return true;
+      }
+    }
+
+    let (Some(key), Some(key_type)) =
+      (hostkey.hostkey(), hostkey.hostkey_type())
+    else {
+      return false;
+    };
+
+    self.known_hosts.iter().any(|known_host| {
+      known_host.key_type == key_type.name()
+        && known_host.key == key
+        && matches_host(&known_host.patterns, hostname)
+    })
+  }
+}
+
+fn parse_line(line: &str) -> Option<KnownHost> {
+  let line = line.trim();
+  if line.is_empty() || line.starts_with('#') {
+    return None;
+  }
+
+  let mut fields = line.split_whitespace();
+  let hosts = fields.next()?;
+
+  if hosts.starts_with('@') {
+    tracing::warn!("ignoring known_hosts marker line {}", hosts);
+    return None;
+  }
+  if hosts.starts_with('|') {
+    tracing::warn!("ignoring hashed known_hosts entry, use plain hostnames");
+    return None;
+  }
+
+  let key_type = fields.next()?.to_string();
+  let key = STANDARD.decode(fields.next()?).ok()?;
+
+  Some(KnownHost {
+    patterns: hosts.split(',').map(str::to_string).collect(),
+    key_type,
+    key,
+  })
+}
+
+fn matches_host(patterns: &[String], hostname: &str) -> bool {
+  let mut matched = false;
+  for pattern in patterns {
+    let (negated, pattern) = match pattern.strip_prefix('!') {
+      Some(pattern) => (true, pattern),
+      None => (false, pattern.as_str()),
+    };
+
+    // libgit2 only hands us the hostname, so ignore non-standard ports
+    let pattern = match pattern.strip_prefix('[') {
+      Some(rest) => rest.split_once("]:").map_or(rest, |(host, _)| host),
+      None => pattern,
+    };
+
+    if wildcard_match(&pattern.to_lowercase(), &hostname.to_lowercase()) {
+      if negated {
+        return false;
+      }
+      matched = true;
+    }
+  }
+  matched
+}
+
+fn wildcard_match(pattern: &str, value: &str) -> bool {
+  match (pattern.chars().next(), value.chars().next()) {
+    (None, None) => true,
+    (Some('*'), next) => {
+      wildcard_match(&pattern[1..], value)
+        || next.is_some_and(|c| wildcard_match(pattern, &value[c.len_utf8()..]))
+    }
+    (Some('?'), Some(c)) => {
+      wildcard_match(&pattern[1..], &value[c.len_utf8()..])
+    }
+    (Some(p), Some(c)) if p == c => {
+      wildcard_match(&pattern[p.len_utf8()..], &value[c.len_utf8()..])
+    }
+    _ => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn patterns(hosts: &str) -> Vec<String> {
+    hosts.split(',').map(str::to_string).collect()
+  }
+
+  #[test]
+  fn wildcards_match_any_characters() {
+    assert!(wildcard_match("*.example.com", "git.example.com"));
+    assert!(wildcard_match("*", ""));
+    assert!(wildcard_match("git?.example.com", "git1.example.com"));
+    assert!(!wildcard_match("git?.example.com", "git.example.com"));
+    assert!(!wildcard_match("*.example.com", "example.org"));
+  }
+
+  #[test]
+  fn hosts_match_case_insensitively() {
+    assert!(matches_host(&patterns("GitHub.com"), "github.com"));
+    assert!(matches_host(
+      &patterns("gitlab.com,github.com"),
+      "github.com"
+    ));
+    assert!(!matches_host(&patterns("gitlab.com"), "github.com"));
+  }
+
+  #[test]
+  fn ports_of_bracketed_hosts_are_ignored() {
+    assert!(matches_host(
+      &patterns("[git.example.com]:2222"),
+      "git.example.com"
+    ));
+    assert!(matches_host(
+      &patterns("[*.example.com]:2222"),
+      "git.example.com"
+    ));
+    assert!(!matches_host(
+      &patterns("[git.example.com]:2222"),
+      "example.com"
+    ));
+  }
+
+  #[test]
+  fn negated_patterns_win() {
+    let patterns = patterns("*.example.com,!internal.example.com");
+    assert!(matches_host(&patterns, "git.example.com"));
+    assert!(!matches_host(&patterns, "internal.example.com"));
+    assert!(!matches_host(
+      &self::patterns("!git.example.com"),
+      "other.com"
+    ));
+  }
+}
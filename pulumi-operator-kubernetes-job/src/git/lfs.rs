@@ -0,0 +1,159 @@
+use std::{
+  collections::BTreeMap,
+  fs::{self, DirBuilder, OpenOptions},
+  io::Write,
+  os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+  path::{Path, PathBuf},
+  process::Command,
+};
+
+use k8s_openapi::ByteString;
+use pulumi_operator_kubernetes::stack::source::git::inner::{
+  GitAuth, GitAuthType,
+};
+
+use crate::git::service::GitError;
+
+const IDENTITY_FILE: &str = "identity";
+const KNOWN_HOSTS_FILE: &str = "known_hosts";
+
+// reads the credentials from the environment, so they never end up in argv
+const CREDENTIAL_HELPER: &str = "credential.helper=!f() { echo \
+                                 \"username=${LFS_USERNAME}\"; echo \
+                                 \"password=${LFS_PASSWORD}\"; }; f";
+
+pub fn pull(
+  workdir: &Path,
+  auth: Option<&GitAuth>,
+  data: Option<&BTreeMap<String, ByteString>>,
+  include: &[String],
+  ca_bundle: Option<&Path>,
+  proxy: Option<&str>,
+) -> Result<(), GitError> {
+  run(git(workdir).arg("lfs").arg("install").arg("--local"))?;
+
+  let mut command = git(workdir);
+  if let Some(ca_bundle) = ca_bundle {
+    command
+      .arg("-c")
+      .arg(format!("http.sslCAInfo={}", ca_bundle.display()));
+  }
+  if let Some(proxy) = proxy {
+    command.arg("-c").arg(format!("http.proxy={}", proxy));
+  }
+  // the ssh key lives outside the checkout and is removed after the pull, so
+  // runtime steps and hooks never see it
+  let mut ssh_dir = None;
+  if let (Some(auth), Some(data)) = (auth, data) {
+    match auth.kind {
+      GitAuthType::Basic | GitAuthType::GitHubApp | GitAuthType::TokenFile => {
+        command
+          .arg("-c")
+          .arg(CREDENTIAL_HELPER)
+          .env("LFS_USERNAME", secret_value(data, "username")?)
+          .env("LFS_PASSWORD", secret_value(data, "password")?);
+      }
+      GitAuthType::Ssh => {
+        let dir = ssh_dir.insert(SshDir::create()?);
+        command.env("GIT_SSH_COMMAND", ssh_command(dir, auth, data)?);
+      }
+    }
+  }
+
+  command.arg("lfs").arg("pull");
+  if !include.is_empty() {
+    command.arg("--include").arg(include.join(","));
+  }
+
+  run(&mut command)
+}
+
+/// Private temporary directory for the ssh files, removed on drop.
+struct SshDir(PathBuf);
+
+impl SshDir {
+  fn create() -> Result<Self, GitError> {
+    let path =
+      std::env::temp_dir().join(format!("lfs-ssh-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    DirBuilder::new()
+      .mode(0o700)
+      .create(&path)
+      .map_err(GitError::Io)?;
+    Ok(Self(path))
+  }
+
+  fn write(&self, name: &str, content: &str) -> Result<PathBuf, GitError> {
+    let path = self.0.join(name);
+    OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .mode(0o600)
+      .open(&path)
+      .and_then(|mut file| file.write_all(content.as_bytes()))
+      .map_err(GitError::Io)?;
+    Ok(path)
+  }
+}
+
+impl Drop for SshDir {
+  fn drop(&mut self) {
+    if let Err(err) = fs::remove_dir_all(&self.0) {
+      tracing::warn!("could not remove {}: {}", self.0.display(), err);
+    }
+  }
+}
+
+fn ssh_command(
+  ssh_dir: &SshDir,
+  auth: &GitAuth,
+  data: &BTreeMap<String, ByteString>,
+) -> Result<String, GitError> {
+  let identity_file =
+    ssh_dir.write(IDENTITY_FILE, &secret_value(data, "identity")?)?;
+
+  let host_key_options =
+    if auth.insecure_skip_host_key_verification.unwrap_or(false) {
+      "-o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null".to_string()
+    } else {
+      let known_hosts = match data.get("known_hosts") {
+        Some(known_hosts) => String::from_utf8(known_hosts.clone().0)?,
+        None => String::new(),
+      };
+      let known_hosts_file = ssh_dir.write(KNOWN_HOSTS_FILE, &known_hosts)?;
+      format!(
+        "-o StrictHostKeyChecking=yes -o UserKnownHostsFile={}",
+        known_hosts_file.display()
+      )
+    };
+
+  Ok(format!(
+    "ssh -i {} -o IdentitiesOnly=yes {}",
+    identity_file.display(),
+    host_key_options
+  ))
+}
+
+fn secret_value(
+  data: &BTreeMap<String, ByteString>,
+  key: &str,
+) -> Result<String, GitError> {
+  let value = data.get(key).ok_or(GitError::DataEmpty)?.clone();
+  Ok(String::from_utf8(value.0)?)
+}
+
+fn git(workdir: &Path) -> Command {
+  let mut command = Command::new("git");
+  command.current_dir(workdir);
+  command
+}
+
+fn run(command: &mut Command) -> Result<(), GitError> {
+  let output = command.output().map_err(GitError::Io)?;
+  if !output.status.success() {
+    return Err(GitError::Lfs(
+      String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    ));
+  }
+  Ok(())
+}
@@ -1 +1,7 @@
+pub mod changes;
+pub mod github_app;
+pub mod host_key;
+pub mod lfs;
 pub mod service;
+pub mod signature;
+pub mod tags;
@@ -1,20 +1,23 @@
-use base64::{decode, encode, Engine};
 use std::{
   collections::BTreeMap,
+  fs,
   path::{Path, PathBuf},
 };
 
-use git2::cert::{CertHostkey, SshHostKeyType};
 use git2::{
-  build::RepoBuilder, CertificateCheckStatus, Cred, FetchOptions,
-  RemoteCallbacks, Repository,
+  build::{CheckoutBuilder, RepoBuilder},
+  CertificateCheckStatus, Cred, FetchOptions, FetchPrune, Oid, ProxyOptions,
+  RemoteCallbacks, Repository, SubmoduleUpdateOptions,
 };
 use k8s_openapi::{api::core::v1::Secret, ByteString};
-use kube::core::ObjectMeta;
 use pulumi_operator_kubernetes::{
   config_provider::{ConfigError, ConfigProvider},
   kubernetes::service::KubernetesService,
-  stack::source::git::inner::{GitAuth, GitAuthType, InnerGitStackSourceSpec},
+  stack::crd::PulumiStack,
+  stack::source::git::inner::{
+    GitAuth, GitAuthType, GitVerify, GitVerifyMode, InnerGitStackSourceSpec,
+  },
+  stack::status::CONDITION_SOURCE_VERIFIED,
   Inst,
 };
 use springtime_di::Component;
@@ -25,10 +28,25 @@ use tokio::{
   task::LocalSet,
 };
 
+use crate::cache::{CacheError, CacheService, GitMirror};
+use crate::fetch_service::FetchedSource;
+use crate::git::changes::ChangeFilter;
+use crate::git::github_app;
+use crate::git::host_key::HostKeyVerifier;
+use crate::git::lfs;
+use crate::git::signature::SignatureVerifier;
+use crate::git::tags::{is_semver_range, resolve_semver_tag};
+use crate::status_service::{StackStatusError, StackStatusService};
+
+const CA_BUNDLE_FILE: &str = "./.ca-bundle.crt";
+const SYSTEM_CA_BUNDLE: &str = "/etc/ssl/certs/ca-certificates.crt";
+
 #[derive(Component)]
 pub struct GitService {
   kubernetes_service: Inst<KubernetesService>,
   config_provider: Inst<ConfigProvider>,
+  stack_status_service: Inst<StackStatusService>,
+  cache_service: Inst<CacheService>,
 }
 
 #[derive(Debug, Error)]
@@ -56,14 +74,58 @@ pub enum GitError {
 
   #[error("Failed to communicate between threads: {0}")]
   Recv(#[from] oneshot::error::RecvError),
+
+  #[error("IO error: {0}")]
+  Io(std::io::Error),
+
+  #[error("Tag verification requires ref to be set to a tag")]
+  TagRefMissing,
+
+  #[error("{0} is not an annotated tag")]
+  NotAnnotatedTag(String),
+
+  #[error("Tag {0} not found")]
+  TagNotFound(String),
+
+  #[error("Invalid semver range {0}: {1}")]
+  SemverRange(String, semver::Error),
+
+  #[error("No tag matches semver range {0}")]
+  NoMatchingTag(String),
+
+  #[error("No signature found on {0}")]
+  SignatureMissing(String),
+
+  #[error("Invalid signature on {0}")]
+  SignatureInvalid(String),
+
+  #[error("Failed to update stack status: {0}")]
+  Status(#[from] StackStatusError),
+
+  #[error("Git LFS failed: {0}")]
+  Lfs(String),
+
+  #[error("Auth kind requires {0} to be set")]
+  AuthConfigMissing(&'static str),
+
+  #[error("Failed to mint GitHub App installation token: {0}")]
+  GitHubApp(String),
+
+  #[error("Invalid watch path {0}: {1}")]
+  WatchPath(String, globset::Error),
+
+  #[error("Cache error: {0}")]
+  Cache(#[from] CacheError),
 }
 
 impl GitService {
   pub async fn fetch(
     &self,
     spec: &InnerGitStackSourceSpec,
-    metadata: &ObjectMeta,
-  ) -> Result<impl Into<PathBuf>, GitError> {
+    pulumi_stack: &PulumiStack,
+  ) -> Result<FetchedSource, GitError> {
+    let metadata = pulumi_stack.metadata.clone();
+    let path = pulumi_stack.spec.path.as_deref();
     let namespace = match &metadata.namespace {
       Some(ns) => ns.clone(),
       None => self.config_provider.operator_namespace()?,
@@ -71,7 +133,33 @@ impl GitService {
 
     let git_controller = GitController {
       kubernetes_service: self.kubernetes_service.clone(),
+      stack_status_service: self.stack_status_service.clone(),
+    };
+    let checkout_settings = CheckoutSettings::new(spec, path);
+    let change_filter = ChangeFilter::new(
+      path,
+      pulumi_stack.spec.watch_paths.as_deref().unwrap_or_default(),
+    )?;
+    // a changed stack spec deploys whatever the revision touches
+    let last_deployed_revision = pulumi_stack
+      .status
+      .as_ref()
+      .filter(|status| {
+        status.last_deployed_generation == pulumi_stack.metadata.generation
+      })
+      .and_then(|status| status.last_deployed_revision.clone());
+    let ca = match &spec.ca_bundle_secret_ref {
+      Some(secret_ref) => {
+        let mut secret =
+          git_controller.get_secret(&namespace, secret_ref).await?;
+        let ca = secret.remove("ca.crt").ok_or_else(|| GitError::DataEmpty)?;
+        Some(ca.0)
+      }
+      None => None,
     };
+    // before the fetch thread starts, see install_ca_bundle
+    let ca_bundle = ca.as_deref().map(install_ca_bundle).transpose()?;
+    let mirror = self.cache_service.git_mirror(&spec.repository).await?;
     let spec = spec.clone();
 
     let (tx, rx) = oneshot::channel();
@@ -82,35 +170,181 @@ impl GitService {
 
       local.spawn_local(async move {
         let res = async move {
-          let mut callback = RemoteCallbacks::new();
-
-          if let Some(auth) = &spec.auth {
-            let data = git_controller.get_secret(&namespace, &auth).await?;
-            git_controller.build_callback(auth, &data, &mut callback)?;
-          }
-
-          let mut fo = FetchOptions::new();
-          fo.remote_callbacks(callback);
+          let data = match &spec.auth {
+            Some(auth) => Some(
+              git_controller
+                .credentials(
+                  &namespace,
+                  auth,
+                  ca.as_deref(),
+                  spec.proxy.as_deref(),
+                )
+                .await?,
+            ),
+            None => None,
+          };
+          let callbacks =
+            || git_controller.callbacks(spec.auth.as_ref(), data.as_ref());
+
+          let verify_tag = matches!(
+            spec.verify,
+            Some(GitVerify {
+              mode: GitVerifyMode::Tag,
+              ..
+            })
+          );
+
+          let semver_range = spec
+            .git_ref
+            .as_deref()
+            .filter(|git_ref| is_semver_range(git_ref));
+          let tag = match semver_range {
+            Some(range) => Some(resolve_semver_tag(
+              &spec.repository,
+              range,
+              callbacks()?,
+              checkout_settings.proxy_options(),
+            )?),
+            None if verify_tag => {
+              Some(spec.git_ref.clone().ok_or(GitError::TagRefMissing)?)
+            }
+            None => None,
+          };
 
           let mut builder = RepoBuilder::new();
-          if let Some(git_ref) = spec.git_ref {
-            builder.branch(&git_ref);
+          // tags can't be checked out as branches, see checkout_tag
+          if let (Some(git_ref), None) = (&spec.git_ref, &tag) {
+            builder.branch(git_ref);
+          }
+          builder.with_checkout(checkout_settings.checkout_builder());
+
+          let repo = match &mirror {
+            Some(mirror) => {
+              update_mirror(
+                mirror,
+                &spec.repository,
+                checkout_settings.remote_options(callbacks()?),
+              )?;
+              // local clones can't be shallow, but they are cheap anyway
+              let repo = builder
+                .clone(&mirror.path.to_string_lossy(), Path::new("./source"))?;
+              repo.remote_set_url("origin", &spec.repository)?;
+              repo
+            }
+            None => {
+              builder
+                .fetch_options(checkout_settings.fetch_options(callbacks()?));
+              builder.clone(spec.repository.as_str(), Path::new("./source"))?
+            }
+          };
+          // everything else fetches from the remote, free the mirror early
+          drop(mirror);
+
+          fetch_git_notes(&repo, &checkout_settings, callbacks()?)?;
+
+          let tag_oid = match &tag {
+            Some(tag) => {
+              Some(checkout_tag(&repo, tag, &checkout_settings, callbacks()?)?)
+            }
+            None => None,
+          };
+
+          let resolved_revision = repo.head()?.peel_to_commit()?.id();
+
+          if let Some(verify) = &spec.verify {
+            let keys = git_controller
+              .get_secret(&namespace, &verify.public_keys_secret_ref)
+              .await?;
+            let result = git_controller.verify(&repo, verify, tag_oid, &keys);
+
+            let (status, reason, message) = match &result {
+              Ok(()) => {
+                (true, "SignatureVerified", "Signature verified".into())
+              }
+              Err(err) => {
+                (false, "SignatureVerificationFailed", err.to_string())
+              }
+            };
+            git_controller
+              .stack_status_service
+              .set_condition(
+                &metadata,
+                CONDITION_SOURCE_VERIFIED,
+                status,
+                reason,
+                message,
+              )
+              .await?;
+            result?;
           }
-          builder.fetch_options(fo);
-
-          let repo =
-            builder.clone(spec.repository.as_str(), Path::new("./source"))?;
 
-          let mut callback = RemoteCallbacks::new();
+          // only report revisions that passed verification
+          let resolved_ref = tag.or_else(|| spec.git_ref.clone());
+          git_controller
+            .stack_status_service
+            .update(&metadata, |status| {
+              status.resolved_ref = resolved_ref;
+              status.resolved_revision = Some(resolved_revision.to_string());
+            })
+            .await?;
+
+          let changed = match &last_deployed_revision {
+            Some(previous)
+              if change_filter.is_active()
+                && *previous != resolved_revision.to_string() =>
+            {
+              match find_previous_commit(
+                &repo,
+                previous,
+                &checkout_settings,
+                callbacks()?,
+              ) {
+                Some(previous) => {
+                  change_filter.changed(&repo, previous, resolved_revision)?
+                }
+                None => {
+                  tracing::warn!(
+                    "last deployed revision {} is not available, running \
+                     anyway",
+                    previous
+                  );
+                  true
+                }
+              }
+            }
+            _ => true,
+          };
+
+          let fetched = FetchedSource {
+            path: "./source".into(),
+            revision: Some(resolved_revision.to_string()),
+            changed,
+          };
+          if !changed {
+            return Ok(fetched);
+          }
 
-          if let Some(auth) = &spec.auth {
-            let data = git_controller.get_secret(&namespace, &auth).await?;
-            git_controller.build_callback(auth, &data, &mut callback)?;
+          if spec.submodules.unwrap_or(false) {
+            update_submodules(
+              &repo,
+              &checkout_settings,
+              &checkout_settings.paths,
+              callbacks,
+            )?;
           }
 
-          fetch_git_notes(&repo, callback)?;
+          if spec.lfs.unwrap_or(false) {
+            lfs::pull(
+              Path::new("./source"),
+              spec.auth.as_ref(),
+              data.as_ref(),
+              &checkout_settings.paths,
+              ca_bundle.as_deref(),
+              spec.proxy.as_deref(),
+            )?;
+          }
 
-          Ok::<&str, GitError>("./source")
+          Ok::<FetchedSource, GitError>(fetched)
         }
         .await;
         tx.send(res)
@@ -123,35 +357,231 @@ impl GitService {
   }
 }
 
+/// Adds the CA bundle to the trusted certificates of libgit2. libgit2 has no
+/// per remote CA setting and ignores http.sslCAInfo, and certificate_check
+/// callbacks only see the leaf certificate without the intermediates the
+/// server sent, so they can't verify the chain themselves.
+fn install_ca_bundle(ca: &[u8]) -> Result<PathBuf, GitError> {
+  // git lfs replaces the system bundle with http.sslCAInfo, keep both in
+  // one file
+  let mut bundle = fs::read(SYSTEM_CA_BUNDLE).unwrap_or_default();
+  bundle.push(b'\n');
+  bundle.extend_from_slice(ca);
+
+  let path = std::env::current_dir()
+    .map_err(GitError::Io)?
+    .join(CA_BUNDLE_FILE);
+  fs::write(&path, bundle).map_err(GitError::Io)?;
+
+  // SAFETY: the option is a process global read by libgit2 without
+  // synchronization. It is set once before the fetch thread starts, the only
+  // thread of the job using libgit2, and the job fetches a single source, so
+  // no other remote picks up the bundle.
+  unsafe { git2::opts::set_ssl_cert_file(&path)? };
+
+  Ok(path)
+}
+
 fn fetch_git_notes(
   repo: &Repository,
-  callback: RemoteCallbacks,
+  checkout_settings: &CheckoutSettings,
+  callback: RemoteCallbacks<'static>,
 ) -> Result<(), git2::Error> {
   let mut remote = repo.find_remote("origin")?;
   let refspec = "refs/notes/*:refs/notes/*";
 
-  // Setup FetchOptions with your callback
-  let mut fetch_options = FetchOptions::new();
-  fetch_options.remote_callbacks(callback);
+  let mut fetch_options = checkout_settings.remote_options(callback);
 
   remote.fetch(&[refspec], Some(&mut fetch_options), None)?;
 
   Ok(())
 }
 
+fn update_mirror(
+  mirror: &GitMirror,
+  repository: &str,
+  mut fetch_options: FetchOptions<'static>,
+) -> Result<(), git2::Error> {
+  let repo = match Repository::open_bare(&mirror.path) {
+    Ok(repo) => repo,
+    Err(_) => Repository::init_bare(&mirror.path)?,
+  };
+
+  let mut remote = repo.remote_anonymous(repository)?;
+  fetch_options.prune(FetchPrune::On);
+  remote.fetch(
+    &[
+      "+refs/heads/*:refs/heads/*",
+      "+refs/tags/*:refs/tags/*",
+      "+refs/notes/*:refs/notes/*",
+    ],
+    Some(&mut fetch_options),
+    None,
+  )?;
+
+  // clones without a ref check out the HEAD of the mirror
+  if let Ok(default_branch) = remote.default_branch() {
+    if let Some(default_branch) = default_branch.as_str() {
+      repo.set_head(default_branch)?;
+    }
+  }
+
+  Ok(())
+}
+
+fn find_previous_commit(
+  repo: &Repository,
+  revision: &str,
+  checkout_settings: &CheckoutSettings,
+  callback: RemoteCallbacks<'static>,
+) -> Option<Oid> {
+  let oid = Oid::from_str(revision).ok()?;
+  if repo.find_commit(oid).is_ok() {
+    return Some(oid);
+  }
+
+  // shallow clones don't contain it, ask the remote for that commit only
+  let mut remote = repo.find_remote("origin").ok()?;
+  let mut fetch_options = checkout_settings.fetch_options(callback);
+  remote
+    .fetch(&[revision], Some(&mut fetch_options), None)
+    .ok()?;
+  repo.find_commit(oid).ok().map(|commit| commit.id())
+}
+
+struct CheckoutSettings {
+  depth: Option<i32>,
+  paths: Vec<String>,
+  proxy: Option<String>,
+}
+
+impl CheckoutSettings {
+  fn new(spec: &InnerGitStackSourceSpec, path: Option<&str>) -> Self {
+    let mut paths = Vec::new();
+    if spec.sparse.unwrap_or(false) {
+      if let Some(path) = path {
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        if !path.is_empty() && path != "." {
+          paths.push(path.to_string());
+          if spec.submodules.unwrap_or(false) {
+            paths.push(".gitmodules".to_string());
+          }
+        }
+      }
+    }
+
+    Self {
+      depth: spec.depth,
+      paths,
+      proxy: spec.proxy.clone(),
+    }
+  }
+
+  fn proxy_options(&self) -> ProxyOptions<'static> {
+    let mut proxy_options = ProxyOptions::new();
+    match &self.proxy {
+      Some(proxy) => proxy_options.url(proxy),
+      // honors http.proxy and the *_proxy environment variables
+      None => proxy_options.auto(),
+    };
+    proxy_options
+  }
+
+  fn remote_options(
+    &self,
+    callback: RemoteCallbacks<'static>,
+  ) -> FetchOptions<'static> {
+    let mut fetch_options = FetchOptions::new();
+    fetch_options.remote_callbacks(callback);
+    fetch_options.proxy_options(self.proxy_options());
+    fetch_options
+  }
+
+  fn fetch_options(
+    &self,
+    callback: RemoteCallbacks<'static>,
+  ) -> FetchOptions<'static> {
+    let mut fetch_options = self.remote_options(callback);
+    if let Some(depth) = self.depth {
+      fetch_options.depth(depth);
+    }
+    fetch_options
+  }
+
+  fn checkout_builder(&self) -> CheckoutBuilder<'static> {
+    let mut checkout_builder = CheckoutBuilder::new();
+    for path in &self.paths {
+      checkout_builder.path(path.as_str());
+    }
+    checkout_builder
+  }
+}
+
+fn update_submodules(
+  repo: &Repository,
+  checkout_settings: &CheckoutSettings,
+  paths: &[String],
+  callbacks: impl Fn() -> Result<RemoteCallbacks<'static>, GitError> + Copy,
+) -> Result<(), GitError> {
+  for mut submodule in repo.submodules()? {
+    if !paths.is_empty()
+      && !paths.iter().any(|path| submodule.path().starts_with(path))
+    {
+      continue;
+    }
+
+    // submodules are fetched in full, the pinned commit may be anywhere
+    let fetch_options = checkout_settings.remote_options(callbacks()?);
+    let mut update_options = SubmoduleUpdateOptions::new();
+    update_options.fetch(fetch_options);
+
+    submodule.update(true, Some(&mut update_options))?;
+    update_submodules(&submodule.open()?, checkout_settings, &[], callbacks)?;
+  }
+  Ok(())
+}
+
+fn checkout_tag(
+  repo: &Repository,
+  tag: &str,
+  checkout_settings: &CheckoutSettings,
+  callback: RemoteCallbacks<'static>,
+) -> Result<Oid, GitError> {
+  let mut remote = repo.find_remote("origin")?;
+  let refspec = format!("+refs/tags/{0}:refs/tags/{0}", tag);
+
+  let mut fetch_options = checkout_settings.fetch_options(callback);
+  remote.fetch(&[refspec], Some(&mut fetch_options), None)?;
+
+  let reference = repo.find_reference(&format!("refs/tags/{}", tag))?;
+  let tag_oid = reference
+    .target()
+    .ok_or_else(|| GitError::TagNotFound(tag.to_string()))?;
+  let commit = reference.peel_to_commit()?;
+
+  repo.checkout_tree(
+    commit.as_object(),
+    Some(checkout_settings.checkout_builder().force()),
+  )?;
+  repo.set_head_detached(commit.id())?;
+
+  Ok(tag_oid)
+}
+
 struct GitController {
   kubernetes_service: Inst<KubernetesService>,
+  stack_status_service: Inst<StackStatusService>,
 }
 
 impl GitController {
   async fn get_secret(
     &self,
     namespace: &str,
-    auth: &GitAuth,
+    name: &str,
   ) -> Result<BTreeMap<String, ByteString>, GitError> {
     let secret = self
       .kubernetes_service
-      .get_in_namespace::<Secret>(namespace, &auth.secret_ref)
+      .get_in_namespace::<Secret>(namespace, name)
       .await?;
 
     let data = secret.data.ok_or_else(|| GitError::DataEmpty)?;
@@ -159,6 +589,85 @@ impl GitController {
     Ok(data)
   }
 
+  /// Resolves the credentials for the auth kind. Token based kinds are
+  /// returned as username and password, like basic auth.
+  async fn credentials(
+    &self,
+    namespace: &str,
+    auth: &GitAuth,
+    ca_bundle: Option<&[u8]>,
+    proxy: Option<&str>,
+  ) -> Result<BTreeMap<String, ByteString>, GitError> {
+    let secret_ref = auth
+      .secret_ref
+      .as_deref()
+      .ok_or(GitError::AuthConfigMissing("secretRef"));
+
+    match auth.kind {
+      GitAuthType::Basic | GitAuthType::Ssh => {
+        self.get_secret(namespace, secret_ref?).await
+      }
+      GitAuthType::GitHubApp => {
+        let app = auth
+          .github_app
+          .as_ref()
+          .ok_or(GitError::AuthConfigMissing("githubApp"))?;
+        let secret = self.get_secret(namespace, secret_ref?).await?;
+        let private_key = secret
+          .get("privateKey")
+          .ok_or_else(|| GitError::DataEmpty)?;
+        let token =
+          github_app::installation_token(app, &private_key.0, ca_bundle, proxy)
+            .await?;
+        Ok(token_credentials(github_app::TOKEN_USERNAME, &token))
+      }
+      GitAuthType::TokenFile => {
+        let token_file = auth
+          .token_file
+          .as_ref()
+          .ok_or(GitError::AuthConfigMissing("tokenFile"))?;
+        // projected tokens rotate, so always read the current one
+        let token = tokio::fs::read_to_string(&token_file.path)
+          .await
+          .map_err(GitError::Io)?;
+        Ok(token_credentials(
+          token_file.username.as_deref().unwrap_or("git"),
+          token.trim(),
+        ))
+      }
+    }
+  }
+
+  fn callbacks(
+    &self,
+    auth: Option<&GitAuth>,
+    data: Option<&BTreeMap<String, ByteString>>,
+  ) -> Result<RemoteCallbacks<'static>, GitError> {
+    let mut callback = RemoteCallbacks::new();
+    if let (Some(auth), Some(data)) = (auth, data) {
+      self.build_callback(auth, data, &mut callback)?;
+    }
+    Ok(callback)
+  }
+
+  fn verify(
+    &self,
+    repo: &Repository,
+    verify: &GitVerify,
+    tag_oid: Option<Oid>,
+    keys: &BTreeMap<String, ByteString>,
+  ) -> Result<(), GitError> {
+    let verifier = SignatureVerifier::new(keys)?;
+    match verify.mode {
+      GitVerifyMode::Head => {
+        verifier.verify_commit(repo, repo.head()?.peel_to_commit()?.id())
+      }
+      GitVerifyMode::Tag => {
+        verifier.verify_tag(repo, tag_oid.ok_or(GitError::TagRefMissing)?)
+      }
+    }
+  }
+
   fn build_callback(
     &self,
     auth: &GitAuth,
@@ -193,25 +702,44 @@ impl GitController {
           None => None,
         };
 
-        //todo: handle known hosts
-        let remote_hash = data
+        let known_hosts = data
+          .get("known_hosts")
+          .map(|known_hosts| String::from_utf8(known_hosts.clone().0))
+          .transpose()?;
+        let fingerprints = data
           .get("remote.pub.sha256")
-          .map(|known_hosts| String::from_utf8(known_hosts.clone().0));
+          .map(|fingerprints| String::from_utf8(fingerprints.clone().0))
+          .transpose()?;
 
-        if let Some(Ok(remote_hash)) = remote_hash {
-          callback.certificate_check(move |cert, hostname| {
-            // if let Some(hostkey) = cert.as_hostkey() {
-            // if remote_hash == encode(hostkey.hash_sha256().unwrap()) {
-            return Ok(CertificateCheckStatus::CertificateOk);
-            // }
-            // }
-            // Err(git2::Error::from_str(&format!(
-            //   "Certificate check failed for host: {}",
-            //   hostname
-            // )))
-          });
+        let verifier =
+          HostKeyVerifier::new(known_hosts.as_deref(), fingerprints.as_deref());
+        let skip_verification =
+          auth.insecure_skip_host_key_verification.unwrap_or(false);
+
+        if skip_verification {
+          tracing::warn!("ssh host key verification is disabled");
         }
 
+        callback.certificate_check(move |cert, hostname| {
+          let Some(hostkey) = cert.as_hostkey() else {
+            return Ok(CertificateCheckStatus::CertificatePassthrough);
+          };
+
+          if skip_verification || verifier.verify(hostname, hostkey) {
+            return Ok(CertificateCheckStatus::CertificateOk);
+          }
+
+          let reason = if verifier.is_empty() {
+            "no known_hosts or remote.pub.sha256 configured in secret"
+          } else {
+            "host key does not match known_hosts or remote.pub.sha256"
+          };
+          Err(git2::Error::from_str(&format!(
+            "SSH host key verification failed for host {}: {}",
+            hostname, reason
+          )))
+        });
+
         callback.credentials(move |_url, username_from_url, _allowed_types| {
           Cred::ssh_key_from_memory(
             username_from_url.unwrap_or(&fallback_username),
@@ -221,7 +749,7 @@ impl GitController {
           )
         });
       }
-      GitAuthType::Basic => {
+      GitAuthType::Basic | GitAuthType::GitHubApp | GitAuthType::TokenFile => {
         let username = data
           .get("username")
           .ok_or_else(|| GitError::DataEmpty)?
@@ -243,3 +771,19 @@ impl GitController {
     Ok(())
   }
 }
+
+fn token_credentials(
+  username: &str,
+  token: &str,
+) -> BTreeMap<String, ByteString> {
+  BTreeMap::from([
+    (
+      "username".to_string(),
+      ByteString(username.as_bytes().to_vec()),
+    ),
+    (
+      "password".to_string(),
+      ByteString(token.as_bytes().to_vec()),
+    ),
+  ])
+}
@@ -0,0 +1,201 @@
+use std::{
+  collections::BTreeMap,
+  fs,
+  io::Write,
+  os::unix::fs::PermissionsExt,
+  process::{Command, Output, Stdio},
+};
+
+use git2::{ErrorCode, Oid, Repository};
+use k8s_openapi::ByteString;
+
+use crate::git::service::GitError;
+
+const GNUPG_HOME: &str = "./.verify-gnupg";
+const ALLOWED_SIGNERS_FILE: &str = "./.verify-allowed-signers";
+const SIGNATURE_FILE: &str = "./.verify-signature";
+const SIGNED_DATA_FILE: &str = "./.verify-signed-data";
+const SSH_PRINCIPAL: &str = "trusted";
+
+const PGP_KEY_MARKER: &str = "-----BEGIN PGP PUBLIC KEY BLOCK-----";
+const PGP_SIGNATURE_MARKER: &[u8] = b"-----BEGIN PGP SIGNATURE-----";
+const SSH_SIGNATURE_MARKER: &[u8] = b"-----BEGIN SSH SIGNATURE-----";
+
+pub struct SignatureVerifier {}
+
+impl SignatureVerifier {
+  pub fn new(data: &BTreeMap<String, ByteString>) -> Result<Self, GitError> {
+    let mut gpg_keys = Vec::new();
+    let mut allowed_signers = String::new();
+
+    for value in data.values() {
+      let keys = String::from_utf8(value.0.clone())?;
+      if keys.contains(PGP_KEY_MARKER) {
+        gpg_keys.push(keys);
+        continue;
+      }
+      for key in keys.lines().map(str::trim) {
+        if key.starts_with("ssh-")
+          || key.starts_with("ecdsa-")
+          || key.starts_with("sk-")
+        {
+          allowed_signers.push_str(&format!("{} {}\n", SSH_PRINCIPAL, key));
+        }
+      }
+    }
+
+    if gpg_keys.is_empty() && allowed_signers.is_empty() {
+      return Err(GitError::DataEmpty);
+    }
+
+    fs::create_dir_all(GNUPG_HOME).map_err(GitError::Io)?;
+    fs::set_permissions(GNUPG_HOME, fs::Permissions::from_mode(0o700))
+      .map_err(GitError::Io)?;
+    for key in gpg_keys {
+      let output = run_with_stdin(
+        Command::new("gpg")
+          .arg("--batch")
+          .arg("--homedir")
+          .arg(GNUPG_HOME)
+          .arg("--import"),
+        key.as_bytes(),
+      )
+      .map_err(GitError::Io)?;
+      if !output.status.success() {
+        return Err(GitError::SignatureInvalid(format!(
+          "failed to import public key: {}",
+          String::from_utf8_lossy(&output.stderr).trim()
+        )));
+      }
+    }
+    fs::write(ALLOWED_SIGNERS_FILE, allowed_signers).map_err(GitError::Io)?;
+
+    Ok(Self {})
+  }
+
+  pub fn verify_commit(
+    &self,
+    repo: &Repository,
+    oid: Oid,
+  ) -> Result<(), GitError> {
+    let (signature, signed_data) = match repo.extract_signature(&oid, None) {
+      Ok(extracted) => extracted,
+      Err(err) if err.code() == ErrorCode::NotFound => {
+        return Err(GitError::SignatureMissing(format!("commit {}", oid)))
+      }
+      Err(err) => return Err(err.into()),
+    };
+
+    self.verify(&signature, &signed_data).map_err(|reason| {
+      GitError::SignatureInvalid(format!("commit {}: {}", oid, reason))
+    })
+  }
+
+  pub fn verify_tag(
+    &self,
+    repo: &Repository,
+    oid: Oid,
+  ) -> Result<(), GitError> {
+    // lightweight tags point straight at the commit and carry no signature
+    if repo.find_tag(oid).is_err() {
+      return Err(GitError::NotAnnotatedTag(oid.to_string()));
+    }
+
+    let odb = repo.odb()?;
+    let object = odb.read(oid)?;
+    let raw = object.data();
+
+    let start = [PGP_SIGNATURE_MARKER, SSH_SIGNATURE_MARKER]
+      .iter()
+      .filter_map(|marker| {
+        raw
+          .windows(marker.len())
+          .position(|window| window == *marker)
+      })
+      .min()
+      .ok_or_else(|| GitError::SignatureMissing(format!("tag {}", oid)))?;
+
+    let (signed_data, signature) = raw.split_at(start);
+
+    self.verify(signature, signed_data).map_err(|reason| {
+      GitError::SignatureInvalid(format!("tag {}: {}", oid, reason))
+    })
+  }
+
+  fn verify(&self, signature: &[u8], signed_data: &[u8]) -> Result<(), String> {
+    let output = if signature.starts_with(PGP_SIGNATURE_MARKER) {
+      fs::write(SIGNATURE_FILE, signature).map_err(|err| err.to_string())?;
+      fs::write(SIGNED_DATA_FILE, signed_data)
+        .map_err(|err| err.to_string())?;
+
+      let output = Command::new("gpg")
+        .arg("--batch")
+        .arg("--homedir")
+        .arg(GNUPG_HOME)
+        .arg("--trust-model")
+        .arg("always")
+        .arg("--status-fd")
+        .arg("1")
+        .arg("--verify")
+        .arg(SIGNATURE_FILE)
+        .arg(SIGNED_DATA_FILE)
+        .output()
+        .map_err(|err| err.to_string())?;
+
+      let good = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| line.starts_with("[GNUPG:] GOODSIG"));
+      if output.status.success() && good {
+        return Ok(());
+      }
+      output
+    } else if signature.starts_with(SSH_SIGNATURE_MARKER) {
+      fs::write(SIGNATURE_FILE, signature).map_err(|err| err.to_string())?;
+
+      let output = run_with_stdin(
+        Command::new("ssh-keygen")
+          .arg("-Y")
+          .arg("verify")
+          .arg("-f")
+          .arg(ALLOWED_SIGNERS_FILE)
+          .arg("-I")
+          .arg(SSH_PRINCIPAL)
+          .arg("-n")
+          .arg("git")
+          .arg("-s")
+          .arg(SIGNATURE_FILE),
+        signed_data,
+      )
+      .map_err(|err| err.to_string())?;
+
+      if output.status.success() {
+        return Ok(());
+      }
+      output
+    } else {
+      return Err("unsupported signature format".into());
+    };
+
+    Err(format!(
+      "signature is not from a trusted key: {}",
+      String::from_utf8_lossy(&output.stderr).trim()
+    ))
+  }
+}
+
+fn run_with_stdin(
+  command: &mut Command,
+  stdin: &[u8],
+) -> Result<Output, std::io::Error> {
+  let mut child = command
+    .stdin(Stdio::piped())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()?;
+
+  if let Some(mut child_stdin) = child.stdin.take() {
+    child_stdin.write_all(stdin)?;
+  }
+
+  child.wait_with_output()
+}
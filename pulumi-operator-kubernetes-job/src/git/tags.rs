@@ -0,0 +1,103 @@
+use git2::{Direction, ProxyOptions, Remote, RemoteCallbacks};
+use semver::{Version, VersionReq};
+
+use crate::git::service::GitError;
+
+pub fn resolve_semver_tag(
+  repository: &str,
+  range: &str,
+  callback: RemoteCallbacks<'static>,
+  proxy_options: ProxyOptions<'static>,
+) -> Result<String, GitError> {
+  let version_req = parse_range(range)
+    .map_err(|err| GitError::SemverRange(range.to_string(), err))?;
+
+  let mut remote = Remote::create_detached(repository)?;
+  let connection = remote.connect_auth(
+    Direction::Fetch,
+    Some(callback),
+    Some(proxy_options),
+  )?;
+
+  connection
+    .list()?
+    .iter()
+    .filter_map(|head| head.name().strip_prefix("refs/tags/"))
+    .filter(|tag| !tag.ends_with("^{}"))
+    .filter_map(|tag| {
+      let version = Version::parse(tag.trim_start_matches('v')).ok()?;
+      version_req
+        .matches(&version)
+        .then(|| (version, tag.to_string()))
+    })
+    .max_by(|(a, _), (b, _)| a.cmp(b))
+    .map(|(_, tag)| tag)
+    .ok_or_else(|| GitError::NoMatchingTag(range.to_string()))
+}
+
+/// Exact versions are plain tags, only operators and wildcards make a range.
+pub fn is_semver_range(git_ref: &str) -> bool {
+  git_ref.starts_with(|c: char| "<>=~^*".contains(c))
+    || git_ref
+      .split(|c: char| c == '.' || c.is_whitespace())
+      .skip(1)
+      .any(|part| matches!(part, "x" | "X" | "*"))
+}
+
+// accepts npm style ranges like ">=1.2.0 <2.0.0" besides ">=1.2.0, <2.0.0"
+fn parse_range(range: &str) -> Result<VersionReq, semver::Error> {
+  let mut comparators: Vec<String> = Vec::new();
+  for token in range
+    .split(|c: char| c == ',' || c.is_whitespace())
+    .filter(|token| !token.is_empty())
+  {
+    match comparators.last_mut() {
+      Some(last) if last.chars().all(|c| "<>=~^".contains(c)) => {
+        last.push_str(token)
+      }
+      _ => comparators.push(token.to_string()),
+    }
+  }
+  VersionReq::parse(&comparators.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn operators_and_wildcards_are_ranges() {
+    for git_ref in ["^1.2", "~1.2.3", ">=1.0.0 <2.0.0", "=1.2.3", "*", "1.x"] {
+      assert!(is_semver_range(git_ref), "{}", git_ref);
+    }
+    assert!(is_semver_range("1.2.*"));
+    assert!(is_semver_range("1.X"));
+  }
+
+  #[test]
+  fn exact_tags_and_branches_are_not_ranges() {
+    for git_ref in ["1.2.3", "v1.2.3", "main", "feature/x-ray", "v1"] {
+      assert!(!is_semver_range(git_ref), "{}", git_ref);
+    }
+  }
+
+  #[test]
+  fn npm_style_ranges_parse_like_comma_separated_ones() {
+    let npm = parse_range(">=1.2.0 <2.0.0").unwrap();
+    let cargo = parse_range(">=1.2.0, <2.0.0").unwrap();
+    assert_eq!(npm, cargo);
+    assert!(npm.matches(&Version::new(1, 9, 0)));
+    assert!(!npm.matches(&Version::new(2, 0, 0)));
+  }
+
+  #[test]
+  fn operators_separated_from_versions_are_joined() {
+    let range = parse_range(">= 1.2.0 < 2.0.0").unwrap();
+    assert_eq!(range, parse_range(">=1.2.0, <2.0.0").unwrap());
+  }
+
+  #[test]
+  fn invalid_ranges_are_rejected() {
+    assert!(parse_range(">=one").is_err());
+  }
+}
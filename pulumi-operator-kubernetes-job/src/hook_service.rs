@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::process::ExitStatus;
+
+use pulumi_cli::{PulumiCLI, StackOutputOptions};
+use pulumi_operator_kubernetes::stack::crd::StackHook;
+use serde_json::Value;
+use springtime_di::Component;
+use thiserror::Error;
+use tokio::process::Command;
+
+const OUTPUTS_VAR: &str = "PULUMI_OUTPUTS";
+const OUTPUT_VAR_PREFIX: &str = "PULUMI_OUTPUT_";
+
+#[derive(Component)]
+pub struct HookService;
+
+#[derive(Debug, Error)]
+pub enum HookError {
+  #[error("{0} hook has an empty command")]
+  EmptyCommand(&'static str),
+
+  #[error("{0} hook `{1}` failed with {2}")]
+  Failed(&'static str, String, ExitStatus),
+
+  #[error("Failed to read stack outputs: {0}")]
+  Outputs(String),
+}
+
+impl HookService {
+  /// Runs the hooks of a phase in order, stopping at the first failure.
+  pub async fn run(
+    &self,
+    pulumi: &PulumiCLI,
+    phase: &'static str,
+    hooks: Option<&Vec<StackHook>>,
+    env: &BTreeMap<String, String>,
+  ) -> Result<(), HookError> {
+    for hook in hooks.into_iter().flatten() {
+      let Some((program, args)) = hook.command.split_first() else {
+        return Err(HookError::EmptyCommand(phase));
+      };
+
+      let step = hook.command.join(" ");
+      tracing::info!("running {} hook `{}`", phase, step);
+
+      let mut command = Command::new(program);
+      command.args(args).envs(env);
+      let status = pulumi.spawn(command).await;
+      if !status.success() {
+        return Err(HookError::Failed(phase, step, status));
+      }
+    }
+
+    Ok(())
+  }
+
+  /// Exposes the stack outputs as `PULUMI_OUTPUTS` and one
+  /// `PULUMI_OUTPUT_<NAME>` variable per output.
+  pub async fn output_env(
+    &self,
+    pulumi: &PulumiCLI,
+    stack: &str,
+  ) -> Result<BTreeMap<String, String>, HookError> {
+    let output = pulumi
+      .stack_output(StackOutputOptions {
+        stack: Some(stack.to_string()),
+        show_secrets: true,
+      })
+      .await;
+    if !output.status.success() {
+      return Err(HookError::Outputs(
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+      ));
+    }
+
+    let outputs: BTreeMap<String, Value> =
+      serde_json::from_slice(&output.stdout)
+        .map_err(|err| HookError::Outputs(err.to_string()))?;
+
+    let mut env = BTreeMap::new();
+    env.insert(
+      OUTPUTS_VAR.to_string(),
+      String::from_utf8_lossy(&output.stdout).trim().to_string(),
+    );
+    for (name, value) in outputs {
+      let name: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+          true => c.to_ascii_uppercase(),
+          false => '_',
+        })
+        .collect();
+      let value = match value {
+        Value::String(value) => value,
+        value => value.to_string(),
+      };
+      env.insert(format!("{}{}", OUTPUT_VAR_PREFIX, name), value);
+    }
+
+    Ok(env)
+  }
+}
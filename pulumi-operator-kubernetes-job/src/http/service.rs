@@ -0,0 +1,150 @@
+use std::{io::Cursor, path::PathBuf};
+
+use flate2::read::GzDecoder;
+use k8s_openapi::api::core::v1::Secret;
+use kube::core::ObjectMeta;
+use pulumi_operator_kubernetes::{
+  config_provider::{ConfigError, ConfigProvider},
+  kubernetes::service::KubernetesService,
+  stack::source::http::inner::{HttpArchiveFormat, InnerHttpStackSourceSpec},
+  Inst,
+};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use sha2::{Digest, Sha256};
+use springtime_di::Component;
+use thiserror::Error;
+
+const SOURCE_DIR: &str = "./source";
+
+#[derive(Component)]
+pub struct HttpService {
+  kubernetes_service: Inst<KubernetesService>,
+  config_provider: Inst<ConfigProvider>,
+}
+
+#[derive(Debug, Error)]
+pub enum HttpError {
+  #[error("Kubernetes error: {0}")]
+  Kubernetes(#[from] kube::Error),
+
+  #[error("Provided secret doesn't contain necessary data")]
+  DataEmpty,
+
+  #[error("Config error: {0}")]
+  Config(#[from] ConfigError),
+
+  #[error("Invalid header {0} in secret")]
+  InvalidHeader(String),
+
+  #[error("Failed to download archive: {0}")]
+  Request(#[from] reqwest::Error),
+
+  #[error("Checksum mismatch, expected sha256 {0} but got {1}")]
+  ChecksumMismatch(String, String),
+
+  #[error("Can't detect archive format of {0}, set format explicitly")]
+  UnknownFormat(String),
+
+  #[error("Failed to extract archive: {0}")]
+  Io(#[from] std::io::Error),
+
+  #[error("Failed to extract zip archive: {0}")]
+  Zip(#[from] zip::result::ZipError),
+
+  #[error("Failed to join extract task: {0}")]
+  Join(#[from] tokio::task::JoinError),
+}
+
+impl HttpService {
+  pub async fn fetch(
+    &self,
+    spec: &InnerHttpStackSourceSpec,
+    metadata: &ObjectMeta,
+  ) -> Result<impl Into<PathBuf>, HttpError> {
+    let format = match &spec.format {
+      Some(format) => format.clone(),
+      None => detect_format(&spec.url)?,
+    };
+
+    let headers = match &spec.headers_secret_ref {
+      Some(secret_ref) => self.headers(metadata, secret_ref).await?,
+      None => HeaderMap::new(),
+    };
+
+    let archive = reqwest::Client::new()
+      .get(&spec.url)
+      .headers(headers)
+      .send()
+      .await?
+      .error_for_status()?
+      .bytes()
+      .await?;
+
+    if let Some(expected) = &spec.sha256 {
+      let expected = expected.trim_start_matches("sha256:").to_lowercase();
+      let actual = hex::encode(Sha256::digest(&archive));
+      if expected != actual {
+        return Err(HttpError::ChecksumMismatch(expected, actual));
+      }
+    }
+
+    tokio::task::spawn_blocking(move || extract(&archive, format)).await??;
+
+    Ok(SOURCE_DIR)
+  }
+
+  async fn headers(
+    &self,
+    metadata: &ObjectMeta,
+    secret_ref: &str,
+  ) -> Result<HeaderMap, HttpError> {
+    let namespace = match &metadata.namespace {
+      Some(ns) => ns.clone(),
+      None => self.config_provider.operator_namespace()?,
+    };
+
+    let data = self
+      .kubernetes_service
+      .get_in_namespace::<Secret>(namespace, secret_ref)
+      .await?
+      .data
+      .ok_or(HttpError::DataEmpty)?;
+
+    // every key of the secret is sent as a header
+    data
+      .into_iter()
+      .map(|(name, value)| {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+          .map_err(|_| HttpError::InvalidHeader(name.clone()))?;
+        let mut header_value = HeaderValue::from_bytes(&value.0)
+          .map_err(|_| HttpError::InvalidHeader(name))?;
+        header_value.set_sensitive(true);
+        Ok((header_name, header_value))
+      })
+      .collect()
+  }
+}
+
+fn detect_format(url: &str) -> Result<HttpArchiveFormat, HttpError> {
+  let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+  if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+    Ok(HttpArchiveFormat::TarGz)
+  } else if path.ends_with(".zip") {
+    Ok(HttpArchiveFormat::Zip)
+  } else {
+    Err(HttpError::UnknownFormat(url.to_string()))
+  }
+}
+
+fn extract(archive: &[u8], format: HttpArchiveFormat) -> Result<(), HttpError> {
+  // both unpack implementations refuse entries escaping the target directory
+  match format {
+    HttpArchiveFormat::TarGz => {
+      tar::Archive::new(GzDecoder::new(archive)).unpack(SOURCE_DIR)?
+    }
+    HttpArchiveFormat::Zip => {
+      zip::ZipArchive::new(Cursor::new(archive))?.extract(SOURCE_DIR)?
+    }
+  }
+  Ok(())
+}
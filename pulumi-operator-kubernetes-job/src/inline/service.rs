@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::core::ObjectMeta;
+use pulumi_operator_kubernetes::{
+  kubernetes::service::KubernetesService,
+  stack::source::inline::{InlineConfigMapRef, InlineProgram},
+  Inst,
+};
+use serde_json::Map;
+use springtime_di::Component;
+use thiserror::Error;
+
+const SOURCE_DIR: &str = "./source";
+const DEFAULT_CONFIG_MAP_KEY: &str = "Pulumi.yaml";
+
+#[derive(Component)]
+pub struct InlineService {
+  kubernetes_service: Inst<KubernetesService>,
+}
+
+#[derive(Debug, Error)]
+pub enum InlineError {
+  #[error("Kubernetes error: {0}")]
+  Kubernetes(#[from] kube::Error),
+
+  #[error("ConfigMap {0} doesn't contain key {1}")]
+  KeyMissing(String, String),
+
+  #[error("Pulumi Stack metadata is missing name or namespace")]
+  MetadataIncomplete,
+
+  #[error("Failed to serialize inline program: {0}")]
+  Serialize(#[from] serde_yaml::Error),
+
+  #[error("Failed to write inline program: {0}")]
+  Io(#[from] std::io::Error),
+}
+
+impl InlineService {
+  pub async fn fetch(
+    &self,
+    program: &InlineProgram,
+    metadata: &ObjectMeta,
+  ) -> Result<impl Into<PathBuf>, InlineError> {
+    let (Some(name), Some(namespace)) = (&metadata.name, &metadata.namespace)
+    else {
+      return Err(InlineError::MetadataIncomplete);
+    };
+
+    let pulumi_yaml = match &program.config_map_ref {
+      Some(config_map_ref) => {
+        if program.resources.is_some() || program.outputs.is_some() {
+          tracing::warn!(
+            "both configMapRef and resources are set, using configMapRef"
+          );
+        }
+        self.read_config_map(namespace, config_map_ref).await?
+      }
+      None => {
+        let mut project = Map::new();
+        project.insert("name".into(), name.as_str().into());
+        project.insert("runtime".into(), "yaml".into());
+        for (key, value) in [
+          ("variables", &program.variables),
+          ("resources", &program.resources),
+          ("outputs", &program.outputs),
+        ] {
+          if let Some(value) = value {
+            project.insert(key.into(), value.clone());
+          }
+        }
+        serde_yaml::to_string(&project)?
+      }
+    };
+
+    tokio::fs::create_dir_all(SOURCE_DIR).await?;
+    tokio::fs::write(Path::new(SOURCE_DIR).join("Pulumi.yaml"), pulumi_yaml)
+      .await?;
+
+    Ok(SOURCE_DIR)
+  }
+
+  async fn read_config_map(
+    &self,
+    namespace: &str,
+    config_map_ref: &InlineConfigMapRef,
+  ) -> Result<String, InlineError> {
+    let key = config_map_ref
+      .key
+      .as_deref()
+      .unwrap_or(DEFAULT_CONFIG_MAP_KEY);
+
+    self
+      .kubernetes_service
+      .get_in_namespace::<ConfigMap>(namespace, &config_map_ref.name)
+      .await?
+      .data
+      .and_then(|mut data| data.remove(key))
+      .ok_or_else(|| {
+        InlineError::KeyMissing(config_map_ref.name.clone(), key.to_string())
+      })
+  }
+}
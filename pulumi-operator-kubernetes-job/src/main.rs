@@ -1,7 +1,15 @@
+pub mod backend_service;
+pub mod cache;
 pub mod fetch_service;
 pub mod git;
+pub mod hook_service;
+pub mod http;
+pub mod inline;
 pub mod oci;
 pub mod pulumi_execution;
+pub mod runtime;
+pub mod secrets_provider_service;
+pub mod status_service;
 
 use springtime::application;
 use springtime_di::instance_provider::ComponentInstancePtr;
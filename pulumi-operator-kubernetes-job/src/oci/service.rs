@@ -1,15 +1,57 @@
 use std::path::PathBuf;
 
+use k8s_openapi::api::core::v1::Secret;
 use kube::core::ObjectMeta;
-use pulumi_operator_kubernetes::stack::source::oci::inner::InnerOciStackSourceSpec;
+use pulumi_operator_kubernetes::{
+  config_provider::{ConfigError, ConfigProvider},
+  kubernetes::service::KubernetesService,
+  stack::source::oci::inner::{InnerOciStackSourceSpec, OciVerify},
+  stack::status::CONDITION_SOURCE_VERIFIED,
+  Inst,
+};
 use springtime_di::Component;
 use thiserror::Error;
+use tokio::process::Command;
+
+use crate::status_service::{StackStatusError, StackStatusService};
+
+const SOURCE_DIR: &str = "./source";
+const PUBLIC_KEY_FILE: &str = "./cosign.pub";
+const PUBLIC_KEY_SECRET_KEY: &str = "cosign.pub";
 
 #[derive(Component)]
-pub struct OciService {}
+pub struct OciService {
+  kubernetes_service: Inst<KubernetesService>,
+  config_provider: Inst<ConfigProvider>,
+  stack_status_service: Inst<StackStatusService>,
+}
 
 #[derive(Debug, Error)]
-pub enum OciError {}
+pub enum OciError {
+  #[error("Kubernetes error: {0}")]
+  Kubernetes(#[from] kube::Error),
+
+  #[error("Provided secret doesn't contain necessary data")]
+  DataEmpty,
+
+  #[error("Config error: {0}")]
+  Config(#[from] ConfigError),
+
+  #[error("Digest {0} is invalid, expected sha256:<hex>")]
+  InvalidDigest(String),
+
+  #[error("Failed to run {0}: {1}")]
+  Io(&'static str, std::io::Error),
+
+  #[error("{0} exited unsuccessfully: {1}")]
+  CommandFailed(&'static str, String),
+
+  #[error("Signature verification failed for {0}: {1}")]
+  VerificationFailed(String, String),
+
+  #[error("Failed to update stack status: {0}")]
+  Status(#[from] StackStatusError),
+}
 
 impl OciService {
   pub async fn fetch(
@@ -17,6 +59,136 @@ impl OciService {
     spec: &InnerOciStackSourceSpec,
     metadata: &ObjectMeta,
   ) -> Result<impl Into<PathBuf>, OciError> {
-    Ok("/")
+    let reference = match &spec.digest {
+      Some(digest) => {
+        if spec.tag.is_some() {
+          tracing::warn!(
+            "both tag and digest are set, using digest {}",
+            digest
+          );
+        }
+        validate_digest(digest)?;
+        format!("{}@{}", spec.url, digest)
+      }
+      None => {
+        format!("{}:{}", spec.url, spec.tag.as_deref().unwrap_or("latest"))
+      }
+    };
+
+    let reference = match &spec.verify {
+      Some(verify) => self.verify(spec, &reference, verify, metadata).await?,
+      None => reference,
+    };
+
+    run(
+      "oras",
+      Command::new("oras")
+        .arg("pull")
+        .arg(&reference)
+        .arg("--output")
+        .arg(SOURCE_DIR),
+    )
+    .await?;
+
+    Ok(SOURCE_DIR)
   }
+
+  async fn verify(
+    &self,
+    spec: &InnerOciStackSourceSpec,
+    reference: &str,
+    verify: &OciVerify,
+    metadata: &ObjectMeta,
+  ) -> Result<String, OciError> {
+    // pin tags to their digest, so the verified artifact is the pulled one
+    let digest =
+      run("oras", Command::new("oras").arg("resolve").arg(reference)).await?;
+    let pinned_reference = format!("{}@{}", spec.url, digest.trim());
+
+    let namespace = match &metadata.namespace {
+      Some(ns) => ns.clone(),
+      None => self.config_provider.operator_namespace()?,
+    };
+
+    let public_key = self
+      .kubernetes_service
+      .get_in_namespace::<Secret>(namespace, &verify.public_key_secret_ref)
+      .await?
+      .data
+      .and_then(|mut data| data.remove(PUBLIC_KEY_SECRET_KEY))
+      .ok_or(OciError::DataEmpty)?;
+
+    tokio::fs::write(PUBLIC_KEY_FILE, public_key.0)
+      .await
+      .map_err(|err| OciError::Io("cosign", err))?;
+
+    match run(
+      "cosign",
+      Command::new("cosign")
+        .arg("verify")
+        .arg("--key")
+        .arg(PUBLIC_KEY_FILE)
+        .arg(&pinned_reference),
+    )
+    .await
+    {
+      Ok(_) => {
+        self
+          .stack_status_service
+          .set_condition(
+            metadata,
+            CONDITION_SOURCE_VERIFIED,
+            true,
+            "SignatureVerified",
+            format!("Signature of {} verified", pinned_reference),
+          )
+          .await?;
+        Ok(pinned_reference)
+      }
+      Err(OciError::CommandFailed(_, output)) => {
+        self
+          .stack_status_service
+          .set_condition(
+            metadata,
+            CONDITION_SOURCE_VERIFIED,
+            false,
+            "SignatureVerificationFailed",
+            &output,
+          )
+          .await?;
+        Err(OciError::VerificationFailed(pinned_reference, output))
+      }
+      Err(err) => Err(err),
+    }
+  }
+}
+
+fn validate_digest(digest: &str) -> Result<(), OciError> {
+  match digest.strip_prefix("sha256:") {
+    Some(hex)
+      if hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) =>
+    {
+      Ok(())
+    }
+    _ => Err(OciError::InvalidDigest(digest.to_string())),
+  }
+}
+
+async fn run(
+  program: &'static str,
+  command: &mut Command,
+) -> Result<String, OciError> {
+  let output = command
+    .output()
+    .await
+    .map_err(|err| OciError::Io(program, err))?;
+
+  if !output.status.success() {
+    return Err(OciError::CommandFailed(
+      program,
+      String::from_utf8_lossy(&output.stderr).trim().to_string(),
+    ));
+  }
+
+  Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
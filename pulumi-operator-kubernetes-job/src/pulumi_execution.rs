@@ -1,41 +1,71 @@
 use futures::task::Spawn;
-use k8s_openapi::api::core::v1::Secret;
 use pulumi_cli::{
-  CancelOptions, LoginOptions, PulumiCLI, StackInitOptions, UpOptions,
+  DestroyOptions, LoginOptions, PulumiCLI, StackInitOptions, UpOptions,
 };
+use pulumi_operator_kubernetes::config_provider::ConfigProvider;
 use pulumi_operator_kubernetes::kubernetes::service::KubernetesService;
 use pulumi_operator_kubernetes::stack::auth::inner::InnerStackAuthSpec;
 use pulumi_operator_kubernetes::stack::auth::repository::StackAuthRepository;
 use pulumi_operator_kubernetes::stack::crd::{
-  PulumiStack, StackAuthRefType, StackSourceRefType,
+  PulumiStack, StackAuthRefType, StackHooks, StackSourceRefType,
 };
 use pulumi_operator_kubernetes::stack::source::git::repository::GitStackSourceRepository;
+use pulumi_operator_kubernetes::stack::source::http::repository::HttpStackSourceRepository;
 use pulumi_operator_kubernetes::stack::source::oci::repository::OciStackSourceRepository;
 use pulumi_operator_kubernetes::stack::source::Source;
+use pulumi_operator_kubernetes::stack::status::{
+  CONDITION_DEPENDENCIES_INSTALLED, CONDITION_INTERRUPTED,
+};
 use pulumi_operator_kubernetes::Inst;
 use serde::Deserialize;
 use springtime::runner::ApplicationRunner;
 use springtime_di::future::{BoxFuture, FutureExt};
-use springtime_di::instance_provider::ErrorPtr;
+use springtime_di::instance_provider::{ComponentInstancePtr, ErrorPtr};
 use springtime_di::{component_alias, Component};
+use std::collections::BTreeMap;
 use std::env::VarError;
 use std::fs::read_to_string;
-use std::process::exit;
+use std::path::Path;
+use std::process::{exit, ExitStatus};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tokio::process::Command;
 
+use crate::backend_service::{BackendError, BackendService};
+use crate::cache::{CacheError, CacheService};
 use crate::fetch_service::{FetchError, FetchService};
+use crate::hook_service::{HookError, HookService};
+use crate::runtime::{
+  PulumiRuntime, RuntimeContext, RuntimeError, RuntimePreparer,
+};
+use crate::secrets_provider_service::{
+  SecretsProviderError, SecretsProviderService,
+};
+use crate::status_service::{StackStatusError, StackStatusService};
 
 #[derive(Component)]
 pub struct PulumiExecution {
   kubernetes_service: Inst<KubernetesService>,
   git_stack_source_repository: Inst<GitStackSourceRepository>,
   oci_stack_source_repository: Inst<OciStackSourceRepository>,
+  http_stack_source_repository: Inst<HttpStackSourceRepository>,
   stack_auth_repository: Inst<StackAuthRepository>,
   fetch_servcice: Inst<FetchService>,
+  stack_status_service: Inst<StackStatusService>,
+  runtime_preparers:
+    Vec<ComponentInstancePtr<dyn RuntimePreparer + Send + Sync>>,
+  cache_service: Inst<CacheService>,
+  hook_service: Inst<HookService>,
+  backend_service: Inst<BackendService>,
+  secrets_provider_service: Inst<SecretsProviderService>,
+  config_provider: Inst<ConfigProvider>,
 }
 
+// leaves time to record the interruption before the pod is killed
+const INTERRUPT_MARGIN: Duration = Duration::from_secs(15);
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+const FAILED_EXIT_CODE: i32 = 1;
+
 #[derive(Debug, Error)]
 pub enum PulumiExecutionError {
   #[error("Pulumi Stack Name is not defined")]
@@ -46,77 +76,84 @@ pub enum PulumiExecutionError {
   PulumiStackNotFound(#[from] kube::Error),
   #[error("Failed to fetch stack source: {0}")]
   StackSourceFetchFailed(#[from] FetchError),
+  #[error("Failed to update stack status: {0}")]
+  StatusUpdateFailed(#[from] StackStatusError),
+  #[error("Pulumi Stack has neither source nor inline program")]
+  StackSourceNotDefined,
+  #[error("Failed to prepare runtime: {0}")]
+  RuntimePreparationFailed(#[from] RuntimeError),
+  #[error("Failed to setup cache: {0}")]
+  CacheSetupFailed(#[from] CacheError),
+  #[error("Stack hook failed: {0}")]
+  HookFailed(#[from] HookError),
+  #[error("Invalid backend configuration: {0}")]
+  BackendConfigInvalid(#[from] BackendError),
+  #[error("Failed to configure secrets provider: {0}")]
+  SecretsProviderFailed(#[from] SecretsProviderError),
 }
 
 #[derive(Deserialize)]
 pub struct PulumiConfig {
-  pub runtime: String,
+  pub runtime: PulumiRuntime,
 }
 
 impl PulumiExecution {
   pub async fn run_internal(&self) -> Result<(), PulumiExecutionError> {
+    self.cache_service.setup()?;
+    let result = self.execute().await;
+
+    // skipped, failed and interrupted runs fill the cache as well
+    if let Err(err) = self.cache_service.evict() {
+      tracing::warn!("failed to evict cache: {}", err);
+    }
+
+    exit(result?);
+  }
+
+  /// Runs the stack and returns the exit code of the job.
+  async fn execute(&self) -> Result<i32, PulumiExecutionError> {
     let pulumi_stack = self.get_stack().await?;
     let inner_stack_source = self.get_inner_stack_source(&pulumi_stack).await?;
     let inner_stack_auth = self.get_inner_stack_auth(&pulumi_stack).await?;
 
     let namespace = std::env::var("WATCH_NAMESPACE")
       .map_err(PulumiExecutionError::CurrentNamespaceNotDefined)?;
-    let access_token = match &inner_stack_auth.access_token_secret {
-      None => None,
-      Some(secret_name) => Some(
-        String::from_utf8(
-          self
-            .kubernetes_service
-            .get_in_namespace::<Secret>(namespace.clone(), secret_name)
-            .await
-            .unwrap()
-            .data
-            .unwrap()
-            .get("token")
-            .unwrap()
-            .0
-            .clone(),
-        )
-        .unwrap(),
-      ),
-    };
+    let access_token = self
+      .backend_service
+      .access_token(&namespace, &inner_stack_auth)
+      .await?;
 
-    if let Some(secret_name) = &inner_stack_auth.backend_auth_secret {
-      let data = self
-        .kubernetes_service
-        .get_in_namespace::<Secret>(namespace, secret_name)
-        .await
-        .unwrap()
-        .data
-        .unwrap();
-
-      let access_key_id =
-        String::from_utf8(data.get("AWS_ACCESS_KEY_ID").unwrap().0.clone())
-          .unwrap();
-      let default_region =
-        String::from_utf8(data.get("AWS_DEFAULT_REGION").unwrap().0.clone())
-          .unwrap();
-      let secret_access_key =
-        String::from_utf8(data.get("AWS_SECRET_ACCESS_KEY").unwrap().0.clone())
-          .unwrap();
-
-      std::env::set_var("AWS_ACCESS_KEY_ID", access_key_id);
-      std::env::set_var("AWS_DEFAULT_REGION", default_region);
-      std::env::set_var("AWS_SECRET_ACCESS_KEY", secret_access_key);
-    }
+    self
+      .backend_service
+      .configure(&namespace, &inner_stack_auth)
+      .await?;
 
     if let Some(access_token) = access_token {
       std::env::set_var("PULUMI_CONFIG_PASSPHRASE", access_token);
     }
 
-    let working_dir = self
+    let fetched = self
       .fetch_servcice
-      .fetch(&inner_stack_source, &pulumi_stack.metadata)
+      .fetch(&inner_stack_source, &pulumi_stack)
       .await?;
+    if !fetched.changed && !is_destroy() {
+      if let Some(revision) = &fetched.revision {
+        tracing::info!("no watched paths changed in {}, skipping", revision);
+        self
+          .stack_status_service
+          .update(&pulumi_stack.metadata, |status| {
+            status.record_skipped_revision(revision);
+          })
+          .await?;
+      }
+      return Ok(0);
+    }
 
-    let working_dir = match &pulumi_stack.spec.path {
-      None => working_dir,
-      Some(path) => working_dir.join(path),
+    let working_dir = fetched.path;
+    let working_dir = match (&inner_stack_source, &pulumi_stack.spec.path) {
+      // inline programs are written to the root of the workdir
+      (Source::Inline(_), _) | (_, None) => working_dir,
+      (_, Some(path)) => working_dir.join(path),
     };
 
     let pulumi_config: PulumiConfig = serde_yaml::from_str(
@@ -126,23 +163,91 @@ impl PulumiExecution {
     )
     .unwrap();
 
-    let pulumi = PulumiCLI::new(working_dir.clone());
+    // the cron job never runs two jobs of a stack at once, so a lock taken by
+    // one of its pods is left behind by an interrupted run, locks of anyone
+    // else are left alone
+    let cron_job = pulumi_stack.metadata.name.clone().unwrap_or_default();
+    let pulumi = PulumiCLI::new(working_dir.clone())
+      .with_interrupt_grace(
+        self
+          .config_provider
+          .cancel_timeout()
+          .saturating_sub(INTERRUPT_MARGIN),
+      )
+      .with_stale_lock_recovery(move |host| is_job_pod(&cron_job, host));
+    let result = self
+      .deploy(
+        &pulumi,
+        &pulumi_stack,
+        inner_stack_auth,
+        &working_dir,
+        &pulumi_config,
+        fetched.revision,
+      )
+      .await;
 
-    match pulumi_config.runtime.as_str() {
-      "nodejs" => {
-        pulumi
-          .spawn({
-            let mut command = Command::new("npm");
-            command.arg("install");
-            command
-          })
-          .await;
-      }
-      _ => {
-        unimplemented!()
-      }
+    let interrupted = pulumi.interrupted();
+    let (reason, message) = match interrupted {
+      true => ("SignalReceived", "The last run was interrupted"),
+      false => ("NotInterrupted", "The last run was not interrupted"),
+    };
+    self
+      .stack_status_service
+      .set_condition(
+        &pulumi_stack.metadata,
+        CONDITION_INTERRUPTED,
+        interrupted,
+        reason,
+        message,
+      )
+      .await?;
+    if interrupted {
+      return Ok(INTERRUPTED_EXIT_CODE);
     }
 
+    // a command killed by a signal has no exit code, it must not succeed
+    Ok(result?.code().unwrap_or(FAILED_EXIT_CODE))
+  }
+
+  async fn deploy(
+    &self,
+    pulumi: &PulumiCLI,
+    pulumi_stack: &PulumiStack,
+    inner_stack_auth: InnerStackAuthSpec,
+    working_dir: &Path,
+    pulumi_config: &PulumiConfig,
+    revision: Option<String>,
+  ) -> Result<ExitStatus, PulumiExecutionError> {
+    let runtime = pulumi_config.runtime.name();
+    let preparer = self
+      .runtime_preparers
+      .iter()
+      .find(|preparer| preparer.runtime() == runtime)
+      .ok_or_else(|| RuntimeError::Unsupported(runtime.to_string()))?;
+    let result = preparer
+      .prepare(&RuntimeContext {
+        pulumi,
+        working_dir,
+        runtime: &pulumi_config.runtime,
+      })
+      .await;
+
+    let (status, reason, message) = match &result {
+      Ok(()) => (true, "InstallSucceeded", "Dependencies installed".into()),
+      Err(err) => (false, "InstallFailed", err.to_string()),
+    };
+    self
+      .stack_status_service
+      .set_condition(
+        &pulumi_stack.metadata,
+        CONDITION_DEPENDENCIES_INSTALLED,
+        status,
+        reason,
+        message,
+      )
+      .await?;
+    result?;
+
     let stack_name = pulumi_stack
       .spec
       .stack_name
@@ -158,15 +263,32 @@ impl PulumiExecution {
     pulumi
       .stack_init(StackInitOptions {
         stack: stack_name.clone(),
-        organization: pulumi_stack.spec.organization.clone()
+        organization: pulumi_stack.spec.organization.clone(),
+        secrets_provider: inner_stack_auth.secrets_provider.clone(),
       })
       .await;
 
-    pulumi
-      .cancel(CancelOptions {
-        stack: Some(stack_name.clone()),
-      })
-      .await;
+    if let Some(secrets_provider) = &inner_stack_auth.secrets_provider {
+      self
+        .secrets_provider_service
+        .ensure(pulumi, &stack_name, secrets_provider)
+        .await?;
+    }
+
+    let hooks = pulumi_stack.spec.hooks.as_ref();
+    if is_destroy() {
+      return self.destroy(pulumi, &stack_name, hooks).await;
+    }
+
+    self
+      .hook_service
+      .run(
+        pulumi,
+        "preUp",
+        hooks.and_then(|hooks| hooks.pre_up.as_ref()),
+        &BTreeMap::new(),
+      )
+      .await?;
 
     let exit = pulumi
       .up(UpOptions {
@@ -176,7 +298,67 @@ impl PulumiExecution {
       })
       .await;
 
-    std::process::exit(exit.code().unwrap_or(0));
+    if let (true, Some(revision)) = (exit.success(), revision) {
+      self
+        .stack_status_service
+        .update(&pulumi_stack.metadata, |status| {
+          status.last_deployed_revision = Some(revision);
+          status.last_deployed_generation = pulumi_stack.metadata.generation;
+        })
+        .await?;
+    }
+
+    let post_up = hooks.and_then(|hooks| hooks.post_up.as_ref());
+    if exit.success() && post_up.is_some() {
+      let env = self.hook_service.output_env(pulumi, &stack_name).await?;
+      self
+        .hook_service
+        .run(pulumi, "postUp", post_up, &env)
+        .await?;
+    }
+
+    Ok(exit)
+  }
+
+  async fn destroy(
+    &self,
+    pulumi: &PulumiCLI,
+    stack_name: &str,
+    hooks: Option<&StackHooks>,
+  ) -> Result<ExitStatus, PulumiExecutionError> {
+    self
+      .hook_service
+      .run(
+        pulumi,
+        "preDestroy",
+        hooks.and_then(|hooks| hooks.pre_destroy.as_ref()),
+        &BTreeMap::new(),
+      )
+      .await?;
+
+    // the outputs are gone after the destroy
+    let post_destroy = hooks.and_then(|hooks| hooks.post_destroy.as_ref());
+    let env = match post_destroy {
+      Some(_) => self.hook_service.output_env(pulumi, stack_name).await?,
+      None => BTreeMap::new(),
+    };
+
+    let exit = pulumi
+      .destroy(DestroyOptions {
+        stack: Some(stack_name.to_string()),
+        yes: true,
+        skip_preview: true,
+      })
+      .await;
+
+    if exit.success() {
+      self
+        .hook_service
+        .run(pulumi, "postDestroy", post_destroy, &env)
+        .await?;
+    }
+
+    Ok(exit)
   }
 
   pub async fn get_stack(&self) -> Result<PulumiStack, PulumiExecutionError> {
@@ -199,7 +381,7 @@ impl PulumiExecution {
     pulumi_stack: &PulumiStack,
   ) -> Result<InnerStackAuthSpec, PulumiExecutionError> {
     let auth_ref = &pulumi_stack.spec.auth;
-    let name = pulumi_stack.metadata.name.clone().unwrap();
+    let name = auth_ref.name.clone();
     let namespace = pulumi_stack.metadata.namespace.clone().unwrap();
 
     Ok(match auth_ref.type_ {
@@ -226,8 +408,19 @@ impl PulumiExecution {
     &self,
     pulumi_stack: &PulumiStack,
   ) -> Result<Source, PulumiExecutionError> {
-    let source_ref = &pulumi_stack.spec.source;
-    let name = pulumi_stack.metadata.name.clone().unwrap();
+    if let Some(program) = &pulumi_stack.spec.inline {
+      if pulumi_stack.spec.source.is_some() {
+        tracing::warn!("both source and inline are set, using inline");
+      }
+      return Ok(program.clone().into());
+    }
+
+    let source_ref = pulumi_stack
+      .spec
+      .source
+      .as_ref()
+      .ok_or(PulumiExecutionError::StackSourceNotDefined)?;
+    let name = source_ref.name.clone();
     let namespace = pulumi_stack.metadata.namespace.clone().unwrap();
     Ok(match source_ref.type_ {
       StackSourceRefType::Git => self
@@ -258,6 +451,20 @@ impl PulumiExecution {
         .spec
         .inner
         .into(),
+      StackSourceRefType::Http => self
+        .http_stack_source_repository
+        .get_namespaced_by_name_and_namespace(&name, &namespace)
+        .await?
+        .spec
+        .inner
+        .into(),
+      StackSourceRefType::ClusterHttp => self
+        .http_stack_source_repository
+        .get_by_name(&name)
+        .await?
+        .spec
+        .inner
+        .into(),
     })
   }
 }
@@ -269,3 +476,48 @@ impl ApplicationRunner for PulumiExecution {
       .boxed()
   }
 }
+
+/// Whether the job destroys the deleted stack instead of deploying it.
+fn is_destroy() -> bool {
+  std::env::var(ConfigProvider::DESTROY_VAR)
+    .is_ok_and(|destroy| destroy == "true")
+}
+
+/// Whether the host is a pod of the cron job, named
+/// `<cron job>-<scheduled minute>-<suffix>`. Long names lose the end of the
+/// minute and the dash before the suffix.
+fn is_job_pod(cron_job: &str, host: &str) -> bool {
+  let Some(rest) = host
+    .strip_prefix(cron_job)
+    .and_then(|rest| rest.strip_prefix('-'))
+  else {
+    return false;
+  };
+  let mut parts = rest.split('-');
+  let minute = parts.next().unwrap_or_default();
+  let digits = minute.chars().take_while(char::is_ascii_digit).count();
+  // pods of a stack named like `<cron job>-<number>` have one more part
+  digits > 0 && parts.count() <= 1
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn job_pods_of_the_cron_job_match() {
+    assert!(is_job_pod("app", "app-28924020-x7k2p"));
+    // the pod name is cut to 63 characters before the random suffix
+    let cron_job = "a".repeat(52);
+    assert!(is_job_pod(&cron_job, &format!("{}-28924x7k2p", cron_job)));
+  }
+
+  #[test]
+  fn other_hosts_do_not_match() {
+    assert!(!is_job_pod("app", "laptop"));
+    assert!(!is_job_pod("app", "app"));
+    assert!(!is_job_pod("app", "application-28924020-x7k2p"));
+    assert!(!is_job_pod("app", "app-2-28924020-x7k2p"));
+    assert!(!is_job_pod("app", "app-ci-runner"));
+  }
+}
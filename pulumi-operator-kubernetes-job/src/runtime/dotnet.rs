@@ -0,0 +1,27 @@
+use springtime_di::future::{BoxFuture, FutureExt};
+use springtime_di::{component_alias, Component};
+use tokio::process::Command;
+
+use super::{check_status, RuntimeContext, RuntimeError, RuntimePreparer};
+
+#[derive(Component)]
+pub struct DotnetRuntimePreparer;
+
+#[component_alias]
+impl RuntimePreparer for DotnetRuntimePreparer {
+  fn runtime(&self) -> &'static str {
+    "dotnet"
+  }
+
+  fn prepare<'a>(
+    &'a self,
+    context: &'a RuntimeContext<'a>,
+  ) -> BoxFuture<'a, Result<(), RuntimeError>> {
+    async {
+      let mut command = Command::new("dotnet");
+      command.arg("restore");
+      check_status("dotnet restore", context.pulumi.spawn(command).await)
+    }
+    .boxed()
+  }
+}
@@ -0,0 +1,36 @@
+use springtime_di::future::{BoxFuture, FutureExt};
+use springtime_di::{component_alias, Component};
+use tokio::process::Command;
+
+use super::{check_status, RuntimeContext, RuntimeError, RuntimePreparer};
+
+#[derive(Component)]
+pub struct GoRuntimePreparer;
+
+#[component_alias]
+impl RuntimePreparer for GoRuntimePreparer {
+  fn runtime(&self) -> &'static str {
+    "go"
+  }
+
+  fn prepare<'a>(
+    &'a self,
+    context: &'a RuntimeContext<'a>,
+  ) -> BoxFuture<'a, Result<(), RuntimeError>> {
+    async {
+      let mut command = Command::new("go");
+      command.arg("mod").arg("download");
+      check_status("go mod download", context.pulumi.spawn(command).await)?;
+
+      // pulumi runs a prebuilt binary instead of building it when set
+      if let Some(binary) = context.runtime.option("binary") {
+        let mut command = Command::new("go");
+        command.arg("build").arg("-o").arg(binary);
+        check_status("go build", context.pulumi.spawn(command).await)?;
+      }
+
+      Ok(())
+    }
+    .boxed()
+  }
+}
@@ -0,0 +1,57 @@
+use springtime_di::future::{BoxFuture, FutureExt};
+use springtime_di::{component_alias, Component};
+use tokio::process::Command;
+
+use super::{check_status, RuntimeContext, RuntimeError, RuntimePreparer};
+
+#[derive(Component)]
+pub struct JavaRuntimePreparer;
+
+#[component_alias]
+impl RuntimePreparer for JavaRuntimePreparer {
+  fn runtime(&self) -> &'static str {
+    "java"
+  }
+
+  fn prepare<'a>(
+    &'a self,
+    context: &'a RuntimeContext<'a>,
+  ) -> BoxFuture<'a, Result<(), RuntimeError>> {
+    async {
+      let working_dir = context.working_dir;
+
+      if working_dir.join("pom.xml").exists() {
+        let mut command = Command::new("mvn");
+        command
+          .arg("--batch-mode")
+          .arg("-DskipTests")
+          .arg("package");
+        return check_status(
+          "mvn package",
+          context.pulumi.spawn(command).await,
+        );
+      }
+
+      if working_dir.join("build.gradle").exists()
+        || working_dir.join("build.gradle.kts").exists()
+      {
+        // prefer the wrapper, it pins the gradle version of the project,
+        // commands run inside the working dir already
+        let mut command = if working_dir.join("gradlew").exists() {
+          Command::new("./gradlew")
+        } else {
+          Command::new("gradle")
+        };
+        command.arg("--no-daemon").arg("assemble");
+        return check_status(
+          "gradle assemble",
+          context.pulumi.spawn(command).await,
+        );
+      }
+
+      // a prebuilt jar set through the binary option needs no build
+      Ok(())
+    }
+    .boxed()
+  }
+}
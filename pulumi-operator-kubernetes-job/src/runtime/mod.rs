@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::ExitStatus;
+
+use pulumi_cli::PulumiCLI;
+use serde::Deserialize;
+use springtime_di::future::BoxFuture;
+use springtime_di::injectable;
+use thiserror::Error;
+
+pub mod dotnet;
+pub mod go;
+pub mod java;
+pub mod nodejs;
+pub mod python;
+pub mod yaml;
+
+#[derive(Debug, Error)]
+pub enum RuntimeError {
+  #[error("Runtime {0} is not supported")]
+  Unsupported(String),
+  #[error("{0} exited with {1}")]
+  CommandFailed(String, ExitStatus),
+  #[error("IO error: {0}")]
+  Io(#[from] std::io::Error),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum PulumiRuntime {
+  Name(String),
+  Object {
+    name: String,
+    #[serde(default)]
+    options: BTreeMap<String, serde_yaml::Value>,
+  },
+}
+
+impl PulumiRuntime {
+  pub fn name(&self) -> &str {
+    match self {
+      PulumiRuntime::Name(name) => name,
+      PulumiRuntime::Object { name, .. } => name,
+    }
+  }
+
+  pub fn option(&self, key: &str) -> Option<&str> {
+    match self {
+      PulumiRuntime::Name(_) => None,
+      PulumiRuntime::Object { options, .. } => {
+        options.get(key).and_then(|value| value.as_str())
+      }
+    }
+  }
+}
+
+pub struct RuntimeContext<'a> {
+  pub pulumi: &'a PulumiCLI,
+  pub working_dir: &'a Path,
+  pub runtime: &'a PulumiRuntime,
+}
+
+/// Restores the dependencies of a Pulumi program before it is run.
+#[injectable]
+pub trait RuntimePreparer {
+  /// The runtime name as used in Pulumi.yaml.
+  fn runtime(&self) -> &'static str;
+
+  fn prepare<'a>(
+    &'a self,
+    context: &'a RuntimeContext<'a>,
+  ) -> BoxFuture<'a, Result<(), RuntimeError>>;
+}
+
+pub fn check_status(
+  step: &str,
+  status: ExitStatus,
+) -> Result<(), RuntimeError> {
+  if status.success() {
+    return Ok(());
+  }
+  Err(RuntimeError::CommandFailed(step.to_string(), status))
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use springtime_di::future::{BoxFuture, FutureExt};
+use springtime_di::{component_alias, Component};
+use tokio::process::Command;
+
+use super::{check_status, RuntimeContext, RuntimeError, RuntimePreparer};
+
+#[derive(Component)]
+pub struct NodejsRuntimePreparer;
+
+enum PackageManager {
+  Npm,
+  Yarn,
+  Pnpm,
+}
+
+impl PackageManager {
+  fn detect(
+    working_dir: &Path,
+    option: Option<&str>,
+  ) -> Result<Self, RuntimeError> {
+    match option {
+      Some("npm") => return Ok(PackageManager::Npm),
+      Some("yarn") => return Ok(PackageManager::Yarn),
+      Some("pnpm") => return Ok(PackageManager::Pnpm),
+      Some(other) => {
+        return Err(RuntimeError::Unsupported(format!(
+          "nodejs with packagemanager {}",
+          other
+        )))
+      }
+      None => {}
+    }
+
+    if working_dir.join("pnpm-lock.yaml").exists() {
+      Ok(PackageManager::Pnpm)
+    } else if working_dir.join("yarn.lock").exists() {
+      Ok(PackageManager::Yarn)
+    } else {
+      Ok(PackageManager::Npm)
+    }
+  }
+
+  fn install_args(
+    &self,
+    working_dir: &Path,
+  ) -> (&'static str, Vec<&'static str>) {
+    let has = |file: &str| working_dir.join(file).exists();
+    match self {
+      PackageManager::Npm if has("package-lock.json") => ("npm", vec!["ci"]),
+      PackageManager::Npm => ("npm", vec!["install"]),
+      // yarn 2+ is configured through .yarnrc.yml and renamed the flag
+      PackageManager::Yarn if has("yarn.lock") && has(".yarnrc.yml") => {
+        ("yarn", vec!["install", "--immutable"])
+      }
+      PackageManager::Yarn if has("yarn.lock") => {
+        ("yarn", vec!["install", "--frozen-lockfile"])
+      }
+      PackageManager::Yarn => ("yarn", vec!["install"]),
+      PackageManager::Pnpm if has("pnpm-lock.yaml") => {
+        ("pnpm", vec!["install", "--frozen-lockfile"])
+      }
+      PackageManager::Pnpm => ("pnpm", vec!["install"]),
+    }
+  }
+}
+
+#[component_alias]
+impl RuntimePreparer for NodejsRuntimePreparer {
+  fn runtime(&self) -> &'static str {
+    "nodejs"
+  }
+
+  fn prepare<'a>(
+    &'a self,
+    context: &'a RuntimeContext<'a>,
+  ) -> BoxFuture<'a, Result<(), RuntimeError>> {
+    async {
+      let package_manager = PackageManager::detect(
+        context.working_dir,
+        context.runtime.option("packagemanager"),
+      )?;
+      let (program, args) = package_manager.install_args(context.working_dir);
+
+      let mut command = Command::new(program);
+      command.args(&args);
+      let step = format!("{} {}", program, args.join(" "));
+      check_status(&step, context.pulumi.spawn(command).await)
+    }
+    .boxed()
+  }
+}
@@ -0,0 +1,60 @@
+use springtime_di::future::{BoxFuture, FutureExt};
+use springtime_di::{component_alias, Component};
+use tokio::process::Command;
+
+use super::{check_status, RuntimeContext, RuntimeError, RuntimePreparer};
+
+#[derive(Component)]
+pub struct PythonRuntimePreparer;
+
+#[component_alias]
+impl RuntimePreparer for PythonRuntimePreparer {
+  fn runtime(&self) -> &'static str {
+    "python"
+  }
+
+  fn prepare<'a>(
+    &'a self,
+    context: &'a RuntimeContext<'a>,
+  ) -> BoxFuture<'a, Result<(), RuntimeError>> {
+    async {
+      let pulumi = context.pulumi;
+      let runtime = context.runtime;
+
+      if runtime.option("toolchain") == Some("poetry") {
+        // pulumi runs the program through poetry, which owns the virtualenv
+        let mut command = Command::new("poetry");
+        command
+          .arg("install")
+          .arg("--no-ansi")
+          .arg("--no-interaction");
+        return check_status("poetry install", pulumi.spawn(command).await);
+      }
+
+      let virtualenv = runtime.option("virtualenv").unwrap_or("venv");
+      let mut command = Command::new("python3");
+      command.arg("-m").arg("venv").arg(virtualenv);
+      check_status("python3 -m venv", pulumi.spawn(command).await)?;
+
+      let virtualenv =
+        std::fs::canonicalize(context.working_dir.join(virtualenv))?;
+      let bin = virtualenv.join("bin");
+
+      if context.working_dir.join("requirements.txt").exists() {
+        let mut command = Command::new(bin.join("pip"));
+        command.arg("install").arg("-r").arg("requirements.txt");
+        check_status("pip install", pulumi.spawn(command).await)?;
+      }
+
+      // without the virtualenv option pulumi uses whatever python is on PATH
+      if runtime.option("virtualenv").is_none() {
+        let path = std::env::var("PATH").unwrap_or_default();
+        std::env::set_var("PATH", format!("{}:{}", bin.display(), path));
+        std::env::set_var("VIRTUAL_ENV", &virtualenv);
+      }
+
+      Ok(())
+    }
+    .boxed()
+  }
+}
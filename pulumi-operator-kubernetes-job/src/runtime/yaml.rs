@@ -0,0 +1,22 @@
+use springtime_di::future::{BoxFuture, FutureExt};
+use springtime_di::{component_alias, Component};
+
+use super::{RuntimeContext, RuntimeError, RuntimePreparer};
+
+#[derive(Component)]
+pub struct YamlRuntimePreparer;
+
+#[component_alias]
+impl RuntimePreparer for YamlRuntimePreparer {
+  fn runtime(&self) -> &'static str {
+    "yaml"
+  }
+
+  fn prepare<'a>(
+    &'a self,
+    _context: &'a RuntimeContext<'a>,
+  ) -> BoxFuture<'a, Result<(), RuntimeError>> {
+    // the yaml language host ships with the pulumi cli
+    async { Ok(()) }.boxed()
+  }
+}
@@ -0,0 +1,76 @@
+use std::process::ExitStatus;
+
+use pulumi_cli::{ChangeSecretsProviderOptions, PulumiCLI, StackExportOptions};
+use serde_json::Value;
+use springtime_di::Component;
+use thiserror::Error;
+
+#[derive(Component)]
+pub struct SecretsProviderService;
+
+#[derive(Debug, Error)]
+pub enum SecretsProviderError {
+  #[error("Changing secrets provider to {0} failed with {1}")]
+  ChangeFailed(String, ExitStatus),
+}
+
+impl SecretsProviderService {
+  /// Migrates the stack to the secrets provider if it uses a different one.
+  pub async fn ensure(
+    &self,
+    pulumi: &PulumiCLI,
+    stack: &str,
+    secrets_provider: &str,
+  ) -> Result<(), SecretsProviderError> {
+    let Some(current) = self.current(pulumi, stack).await else {
+      tracing::info!("no secrets provider recorded for {}", stack);
+      return Ok(());
+    };
+    if current == secrets_provider {
+      return Ok(());
+    }
+
+    tracing::info!(
+      "changing secrets provider of {} from {} to {}",
+      stack,
+      current,
+      secrets_provider
+    );
+    let status = pulumi
+      .change_secrets_provider(ChangeSecretsProviderOptions {
+        stack: Some(stack.to_string()),
+        secrets_provider: secrets_provider.to_string(),
+      })
+      .await;
+    if !status.success() {
+      return Err(SecretsProviderError::ChangeFailed(
+        secrets_provider.to_string(),
+        status,
+      ));
+    }
+
+    Ok(())
+  }
+
+  // reads the provider from the exported deployment, named like the
+  // --secrets-provider argument
+  async fn current(&self, pulumi: &PulumiCLI, stack: &str) -> Option<String> {
+    let output = pulumi
+      .stack_export(StackExportOptions {
+        stack: Some(stack.to_string()),
+      })
+      .await;
+    if !output.status.success() {
+      return None;
+    }
+
+    let export: Value = serde_json::from_slice(&output.stdout).ok()?;
+    let provider = &export["deployment"]["secrets_providers"];
+    match provider["type"].as_str()? {
+      "passphrase" => Some("passphrase".into()),
+      "service" => Some("default".into()),
+      "cloud" => provider["state"]["url"].as_str().map(Into::into),
+      _ => None,
+    }
+  }
+}
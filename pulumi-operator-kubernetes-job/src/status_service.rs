@@ -0,0 +1,71 @@
+use kube::core::ObjectMeta;
+use pulumi_operator_kubernetes::kubernetes::service::KubernetesService;
+use pulumi_operator_kubernetes::stack::crd::PulumiStack;
+use pulumi_operator_kubernetes::stack::status::StackStatus;
+use pulumi_operator_kubernetes::Inst;
+use springtime_di::Component;
+use thiserror::Error;
+
+#[derive(Component)]
+pub struct StackStatusService {
+  kubernetes_service: Inst<KubernetesService>,
+}
+
+#[derive(Debug, Error)]
+pub enum StackStatusError {
+  #[error("Kubernetes error: {0}")]
+  Kubernetes(#[from] kube::Error),
+
+  #[error("Pulumi Stack metadata is missing name or namespace")]
+  MetadataIncomplete,
+
+  #[error("Failed to serialize stack status: {0}")]
+  Serialize(#[from] serde_json::Error),
+}
+
+impl StackStatusService {
+  pub async fn update(
+    &self,
+    metadata: &ObjectMeta,
+    update: impl FnOnce(&mut StackStatus),
+  ) -> Result<(), StackStatusError> {
+    let (Some(name), Some(namespace)) = (&metadata.name, &metadata.namespace)
+    else {
+      return Err(StackStatusError::MetadataIncomplete);
+    };
+
+    // always start from the latest status, other steps may have changed it
+    let stack: PulumiStack = self
+      .kubernetes_service
+      .get_in_namespace(namespace, name)
+      .await?;
+
+    let mut status = stack.status.clone().unwrap_or_default();
+    update(&mut status);
+
+    if stack.status.as_ref() != Some(&status) {
+      self
+        .kubernetes_service
+        .patch_status(&stack, serde_json::to_value(&status)?)
+        .await?;
+    }
+
+    Ok(())
+  }
+
+  pub async fn set_condition(
+    &self,
+    metadata: &ObjectMeta,
+    type_: &str,
+    status: bool,
+    reason: &str,
+    message: impl ToString,
+  ) -> Result<(), StackStatusError> {
+    let generation = metadata.generation;
+    self
+      .update(metadata, |stack_status| {
+        stack_status.set_condition(type_, status, reason, message, generation)
+      })
+      .await
+  }
+}
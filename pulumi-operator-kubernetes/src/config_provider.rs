@@ -1,21 +1,221 @@
 use std::env::VarError;
+use std::path::PathBuf;
+use std::sync::{RwLock, RwLockReadGuard};
+use std::time::Duration;
 
+use serde::de::DeserializeOwned;
 use springtime_di::Component;
 use thiserror::Error;
 
+use crate::operator_config::{CacheDefaults, JobConfig, OperatorConfig};
+use crate::stack::crd::{MainContainerOverride, MainPodOverride};
+
+/// Settings of the loaded operator configuration win over the environment.
 #[derive(Component)]
-pub struct ConfigProvider {}
+pub struct ConfigProvider {
+  #[component(default)]
+  config: RwLock<OperatorConfig>,
+}
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
   #[error("Failed to read environment variable: {0}")]
   Var(#[from] VarError),
+
+  #[error("Invalid JSON in {0}: {1}")]
+  Json(&'static str, serde_json::Error),
 }
 
 impl ConfigProvider {
   pub const OPERATOR_NS_VAR: &'static str = "OPERATOR_NAMESPACE";
+  pub const CACHE_DIR_VAR: &'static str = "PULUMI_CACHE_DIR";
+  pub const CACHE_MAX_SIZE_VAR: &'static str = "PULUMI_CACHE_MAX_SIZE";
+  pub const DEFAULT_CACHE_CLAIM_VAR: &'static str = "DEFAULT_CACHE_CLAIM_NAME";
+  pub const DEFAULT_CACHE_MAX_SIZE_VAR: &'static str = "DEFAULT_CACHE_MAX_SIZE";
+  pub const CANCEL_TIMEOUT_VAR: &'static str = "STACK_CANCEL_TIMEOUT_SECONDS";
+  pub const DEFAULT_MAIN_CONTAINER_VAR: &'static str = "DEFAULT_MAIN_CONTAINER";
+  pub const DEFAULT_MAIN_POD_VAR: &'static str = "DEFAULT_MAIN_POD";
+  pub const DELETE_LEGACY_RESOURCES_VAR: &'static str =
+    "DELETE_LEGACY_RESOURCES";
+  pub const NATIVE_SIDECARS_VAR: &'static str = "NATIVE_SIDECARS";
+  /// Set on the job that destroys a deleted stack.
+  pub const DESTROY_VAR: &'static str = "PULUMI_DESTROY";
+
+  // the timeout the operator always used for pulumi to stop
+  const DEFAULT_CANCEL_TIMEOUT: Duration = Duration::from_secs(1800);
+  const DEFAULT_REQUEUE: Duration = Duration::from_secs(15);
+  const DEFAULT_JOB_IMAGE: &'static str =
+    "ghcr.io/stromee/pulumi-operator/pulumi-operator-kubernetes-job:1.0.31";
+  const DEFAULT_JOB_SCHEDULE: &'static str = "* * * * *";
+  const DEFAULT_JOB_ACTIVE_DEADLINE_SECONDS: i64 = 60 * 60;
+  const DEFAULT_JOB_BACKOFF_LIMIT: i32 = 10000;
+  const DEFAULT_JOB_HISTORY_LIMIT: i32 = 1;
+  const DEFAULT_JOB_LOG_LEVEL: &'static str = "trace";
+
+  /// Replaces the configuration, returns whether it changed.
+  pub fn set_config(&self, config: OperatorConfig) -> bool {
+    let mut current = self.config.write().unwrap();
+    let changed = *current != config;
+    *current = config;
+    changed
+  }
+
+  fn config(&self) -> RwLockReadGuard<'_, OperatorConfig> {
+    self.config.read().unwrap()
+  }
+
+  fn job<T>(&self, field: impl Fn(&JobConfig) -> Option<T>) -> Option<T> {
+    self.config().job.as_ref().and_then(field)
+  }
+
+  fn cache<T>(&self, field: impl Fn(&CacheDefaults) -> Option<T>) -> Option<T> {
+    self.config().cache.as_ref().and_then(field)
+  }
+
+  pub fn job_image(&self) -> String {
+    self
+      .job(|job| job.image.clone())
+      .unwrap_or_else(|| Self::DEFAULT_JOB_IMAGE.into())
+  }
+
+  pub fn job_schedule(&self) -> String {
+    self
+      .job(|job| job.schedule.clone())
+      .unwrap_or_else(|| Self::DEFAULT_JOB_SCHEDULE.into())
+  }
+
+  pub fn job_active_deadline_seconds(&self) -> i64 {
+    self
+      .job(|job| job.active_deadline_seconds)
+      .unwrap_or(Self::DEFAULT_JOB_ACTIVE_DEADLINE_SECONDS)
+  }
+
+  pub fn job_backoff_limit(&self) -> i32 {
+    self
+      .job(|job| job.backoff_limit)
+      .unwrap_or(Self::DEFAULT_JOB_BACKOFF_LIMIT)
+  }
+
+  pub fn successful_jobs_history_limit(&self) -> i32 {
+    self
+      .job(|job| job.successful_jobs_history_limit)
+      .unwrap_or(Self::DEFAULT_JOB_HISTORY_LIMIT)
+  }
+
+  pub fn failed_jobs_history_limit(&self) -> i32 {
+    self
+      .job(|job| job.failed_jobs_history_limit)
+      .unwrap_or(Self::DEFAULT_JOB_HISTORY_LIMIT)
+  }
+
+  pub fn job_log_level(&self) -> String {
+    self
+      .job(|job| job.log_level.clone())
+      .unwrap_or_else(|| Self::DEFAULT_JOB_LOG_LEVEL.into())
+  }
+
+  /// Whether extra containers of stack jobs are native sidecars.
+  pub fn native_sidecars(&self) -> bool {
+    self
+      .job(|job| job.native_sidecars)
+      .or_else(|| {
+        std::env::var(Self::NATIVE_SIDECARS_VAR)
+          .ok()
+          .and_then(|value| value.parse().ok())
+      })
+      .unwrap_or(false)
+  }
+
+  /// Whether orphaned resources of older operator versions are deleted or
+  /// only logged.
+  pub fn delete_legacy_resources(&self) -> bool {
+    self
+      .config()
+      .delete_legacy_resources
+      .or_else(|| {
+        std::env::var(Self::DELETE_LEGACY_RESOURCES_VAR)
+          .ok()
+          .and_then(|value| value.parse().ok())
+      })
+      .unwrap_or(false)
+  }
+
+  /// Delay before a failed reconciliation is retried.
+  pub fn requeue_interval(&self) -> Duration {
+    self
+      .config()
+      .requeue_seconds
+      .map(Duration::from_secs)
+      .unwrap_or(Self::DEFAULT_REQUEUE)
+  }
 
   pub fn operator_namespace(&self) -> Result<String, ConfigError> {
     Ok(std::env::var(Self::OPERATOR_NS_VAR)?)
   }
+
+  pub fn cache_dir(&self) -> Option<PathBuf> {
+    std::env::var_os(Self::CACHE_DIR_VAR).map(PathBuf::from)
+  }
+
+  pub fn cache_max_size(&self) -> Option<String> {
+    std::env::var(Self::CACHE_MAX_SIZE_VAR).ok()
+  }
+
+  pub fn default_cache_claim_name(&self) -> Option<String> {
+    self
+      .cache(|cache| cache.claim_name.clone())
+      .or_else(|| std::env::var(Self::DEFAULT_CACHE_CLAIM_VAR).ok())
+  }
+
+  pub fn default_cache_max_size(&self) -> Option<String> {
+    self
+      .cache(|cache| cache.max_size.clone())
+      .map(|max_size| max_size.0)
+      .or_else(|| std::env::var(Self::DEFAULT_CACHE_MAX_SIZE_VAR).ok())
+  }
+
+  /// How long a running stack job gets to stop pulumi when it is cancelled.
+  pub fn cancel_timeout(&self) -> Duration {
+    self
+      .config()
+      .cancel_timeout_seconds
+      .or_else(|| {
+        std::env::var(Self::CANCEL_TIMEOUT_VAR)
+          .ok()
+          .and_then(|seconds| seconds.parse().ok())
+      })
+      .map(Duration::from_secs)
+      .unwrap_or(Self::DEFAULT_CANCEL_TIMEOUT)
+  }
+
+  /// Operator-wide container overrides, as JSON like `spec.mainContainer`.
+  pub fn default_main_container(
+    &self,
+  ) -> Result<MainContainerOverride, ConfigError> {
+    let defaults = Self::json_var(Self::DEFAULT_MAIN_CONTAINER_VAR)?;
+    Ok(match self.config().main_container.clone() {
+      Some(main_container) => main_container.with_defaults(defaults),
+      None => defaults,
+    })
+  }
+
+  /// Operator-wide pod overrides, as JSON like `spec.mainPod`.
+  pub fn default_main_pod(&self) -> Result<MainPodOverride, ConfigError> {
+    let defaults = Self::json_var(Self::DEFAULT_MAIN_POD_VAR)?;
+    Ok(match self.config().main_pod.clone() {
+      Some(main_pod) => main_pod.with_defaults(defaults),
+      None => defaults,
+    })
+  }
+
+  fn json_var<T: DeserializeOwned + Default>(
+    var: &'static str,
+  ) -> Result<T, ConfigError> {
+    match std::env::var(var) {
+      Ok(value) => {
+        serde_json::from_str(&value).map_err(|err| ConfigError::Json(var, err))
+      }
+      Err(_) => Ok(T::default()),
+    }
+  }
 }
@@ -11,11 +11,14 @@ use springtime_di::{component_alias, Component};
 use crate::kubernetes::service::{
   KubernetesCrdInstallError, KubernetesService,
 };
+use crate::operator_config::crd::PulumiOperatorConfig as PulumiOperatorConfigCrd;
 use crate::stack::auth::cluster_crd::ClusterStackAuth as ClusterStackAuthCrd;
 use crate::stack::auth::crd::StackAuth as StackAuthCrd;
 use crate::stack::crd::PulumiStack as PulumiStackCrd;
 use crate::stack::source::git::cluster_crd::ClusterGitStackSource as ClusterGitStackSourceCrd;
 use crate::stack::source::git::crd::GitStackSource as GitStackSourceCrd;
+use crate::stack::source::http::cluster_crd::ClusterHttpStackSource as ClusterHttpStackSourceCrd;
+use crate::stack::source::http::crd::HttpStackSource as HttpStackSourceCrd;
 use crate::stack::source::oci::cluster_crd::ClusterOciStackSource as ClusterOciStackSourceCrd;
 use crate::stack::source::oci::crd::OciStackSource as OciStackSourceCrd;
 
@@ -51,6 +54,16 @@ impl PulumiStackCrdInstaller {
       .install_crd(OciStackSourceCrd::crd())
       .await?;
 
+    self
+      .kubernetes_service
+      .install_crd(ClusterHttpStackSourceCrd::crd())
+      .await?;
+
+    self
+      .kubernetes_service
+      .install_crd(HttpStackSourceCrd::crd())
+      .await?;
+
     self
       .kubernetes_service
       .install_crd(ClusterStackAuthCrd::crd())
@@ -61,6 +74,11 @@ impl PulumiStackCrdInstaller {
       .install_crd(StackAuthCrd::crd())
       .await?;
 
+    self
+      .kubernetes_service
+      .install_crd(PulumiOperatorConfigCrd::crd())
+      .await?;
+
     Ok(())
   }
 }
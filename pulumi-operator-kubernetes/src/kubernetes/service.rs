@@ -6,6 +6,7 @@ use k8s_openapi::{ClusterResourceScope, NamespaceResourceScope};
 use kube::api::{ObjectList, Patch, PatchParams, PostParams};
 use kube::{Api, Resource, ResourceExt};
 use serde::de::DeserializeOwned;
+use serde::Serialize;
 use springtime_di::Component;
 use thiserror::Error;
 
@@ -25,6 +26,8 @@ pub enum KubernetesCrdInstallError {
 }
 
 impl KubernetesService {
+  pub const FIELD_MANAGER: &'static str = "pulumi-operator";
+
   pub async fn install_crd(
     &self,
     crd: CustomResourceDefinition,
@@ -107,6 +110,15 @@ impl KubernetesService {
     Api::all(self.client_provider.get().await)
   }
 
+  pub async fn cluster_api<K>(&self) -> Api<K>
+  where
+    K:
+      Resource<Scope = ClusterResourceScope> + Clone + DeserializeOwned + Debug,
+    <K as Resource>::DynamicType: Default,
+  {
+    Api::all(self.client_provider.get().await)
+  }
+
   pub async fn all<K>(&self) -> Result<ObjectList<K>, kube::Error>
   where
     K: Resource<Scope = NamespaceResourceScope>
@@ -276,6 +288,61 @@ impl KubernetesService {
     Ok(())
   }
 
+  pub async fn patch_status<K>(
+    &self,
+    resource: &K,
+    status: serde_json::Value,
+  ) -> Result<K, kube::Error>
+  where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+      + Clone
+      + DeserializeOwned
+      + Debug,
+  {
+    let client = self.client_provider.get().await;
+    let api: Api<K> = if let Some(namespace) = &resource.namespace() {
+      Api::namespaced(client.clone(), namespace)
+    } else {
+      Api::all(client.clone())
+    };
+
+    let patch = serde_json::json!({ "status": status });
+
+    api
+      .patch_status(
+        &resource.meta().name.clone().expect("name is empty"),
+        &PatchParams::default(),
+        &Patch::Merge(&patch),
+      )
+      .await
+  }
+
+  /// Server-side applies the resource. Unchanged resources are left as is.
+  pub async fn apply<K>(&self, resource: &K) -> Result<K, kube::Error>
+  where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+      + Clone
+      + DeserializeOwned
+      + Serialize
+      + Debug,
+  {
+    let client = self.client_provider.get().await;
+    let api: Api<K> = if let Some(namespace) = &resource.namespace() {
+      Api::namespaced(client.clone(), namespace)
+    } else {
+      Api::all(client.clone())
+    };
+
+    // forced, objects created by older versions are owned by other managers
+    api
+      .patch(
+        &resource.meta().name.clone().expect("name is empty"),
+        &PatchParams::apply(Self::FIELD_MANAGER).force(),
+        &Patch::Apply(resource),
+      )
+      .await
+  }
+
   pub async fn has_finalizer<K>(
     &self,
     resource: &K,
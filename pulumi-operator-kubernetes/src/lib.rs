@@ -2,6 +2,7 @@ use springtime_di::instance_provider::ComponentInstancePtr;
 
 pub mod config_provider;
 pub mod kubernetes;
+pub mod operator_config;
 pub mod stack;
 
 pub fn bind() {}
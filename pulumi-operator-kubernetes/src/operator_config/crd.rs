@@ -0,0 +1,18 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::OperatorConfig;
+
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[kube(
+  group = "pulumi.stromee.de",
+  version = "v1",
+  kind = "PulumiOperatorConfig",
+  plural = "pulumioperatorconfigs"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct PulumiOperatorConfigSpec {
+  #[serde(flatten)]
+  pub inner: OperatorConfig,
+}
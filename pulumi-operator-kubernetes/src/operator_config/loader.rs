@@ -0,0 +1,100 @@
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use springtime_di::Component;
+use thiserror::Error;
+
+use crate::config_provider::ConfigProvider;
+use crate::kubernetes::service::KubernetesService;
+use crate::Inst;
+
+use super::crd::PulumiOperatorConfig;
+use super::{OperatorConfig, OperatorConfigError};
+
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+pub enum OperatorConfigLoadError {
+  #[error("could not read operator configuration {0}: {1}")]
+  Io(PathBuf, std::io::Error),
+
+  #[error("invalid YAML in operator configuration {0}: {1}")]
+  Yaml(PathBuf, serde_yaml::Error),
+
+  #[error("could not read PulumiOperatorConfig: {0}")]
+  Kubernetes(#[from] kube::Error),
+
+  #[error("invalid operator configuration: {0}")]
+  Invalid(#[from] OperatorConfigError),
+}
+
+/// Loads the operator configuration from the file and the cluster resource
+/// into the ConfigProvider, the cluster resource wins.
+#[derive(Component)]
+pub struct OperatorConfigLoader {
+  config_provider: Inst<ConfigProvider>,
+  kubernetes_service: Inst<KubernetesService>,
+}
+
+impl OperatorConfigLoader {
+  pub const CONFIG_FILE_VAR: &'static str = "OPERATOR_CONFIG_FILE";
+  pub const RESOURCE_NAME: &'static str = "default";
+
+  pub async fn load(&self) -> Result<OperatorConfig, OperatorConfigLoadError> {
+    let mut config = OperatorConfig::default();
+
+    if let Some(path) = std::env::var_os(Self::CONFIG_FILE_VAR) {
+      let path = PathBuf::from(path);
+      let content = tokio::fs::read_to_string(&path)
+        .await
+        .map_err(|err| OperatorConfigLoadError::Io(path.clone(), err))?;
+      let file: OperatorConfig = serde_yaml::from_str(&content)
+        .map_err(|err| OperatorConfigLoadError::Yaml(path, err))?;
+      config = config.merge(file);
+    }
+
+    match self
+      .kubernetes_service
+      .get::<PulumiOperatorConfig>(Self::RESOURCE_NAME)
+      .await
+    {
+      Ok(resource) => config = config.merge(resource.spec.inner),
+      // neither the resource nor its definition have to exist
+      Err(kube::Error::Api(err)) if err.code == 404 => {}
+      Err(err) => return Err(err.into()),
+    }
+
+    config.validate()?;
+    Ok(config)
+  }
+
+  /// Loads the configuration, returns whether it changed.
+  pub async fn reload(&self) -> Result<bool, OperatorConfigLoadError> {
+    let config = self.load().await?;
+    Ok(self.config_provider.set_config(config))
+  }
+
+  /// Reloads periodically and calls `on_change` after the configuration
+  /// changed, an invalid configuration keeps the previous one.
+  pub async fn run<F>(&self, on_change: impl Fn() -> F)
+  where
+    F: Future<Output = ()>,
+  {
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    interval.tick().await;
+    loop {
+      interval.tick().await;
+      match self.reload().await {
+        Ok(true) => {
+          tracing::info!("operator configuration changed");
+          on_change().await;
+        }
+        Ok(false) => {}
+        Err(err) => {
+          tracing::warn!("keeping previous operator configuration: {}", err)
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,289 @@
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::stack::crd::{MainContainerOverride, MainPodOverride};
+
+pub mod crd;
+pub mod loader;
+
+/// Operator-wide settings, every unset field falls back to the environment
+/// and then to the built-in default.
+#[derive(
+  Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct OperatorConfig {
+  pub job: Option<JobConfig>,
+  /// How long a running stack job gets to stop pulumi when it is cancelled.
+  pub cancel_timeout_seconds: Option<u64>,
+  /// Delay before a failed reconciliation is retried.
+  pub requeue_seconds: Option<u64>,
+  pub cache: Option<CacheDefaults>,
+  pub main_container: Option<MainContainerOverride>,
+  pub main_pod: Option<MainPodOverride>,
+  /// Deletes resources of stacks created by older operator versions instead
+  /// of only logging them.
+  pub delete_legacy_resources: Option<bool>,
+}
+
+#[derive(
+  Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct JobConfig {
+  pub image: Option<String>,
+  pub schedule: Option<String>,
+  pub active_deadline_seconds: Option<i64>,
+  pub backoff_limit: Option<i32>,
+  pub successful_jobs_history_limit: Option<i32>,
+  pub failed_jobs_history_limit: Option<i32>,
+  /// `RUST_LOG` of the job container.
+  pub log_level: Option<String>,
+  /// Renders extra containers as native sidecars, which stop with the job.
+  /// Needs Kubernetes 1.29 or 1.28 with the SidecarContainers feature gate,
+  /// older clusters would never start the pulumi container.
+  pub native_sidecars: Option<bool>,
+}
+
+#[derive(
+  Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheDefaults {
+  pub claim_name: Option<String>,
+  pub max_size: Option<Quantity>,
+}
+
+#[derive(Debug, Error)]
+pub enum OperatorConfigError {
+  #[error("{0} must not be empty")]
+  Empty(&'static str),
+
+  #[error("{0} must be at least {1}")]
+  TooSmall(&'static str, i64),
+
+  #[error("invalid cron schedule {0:?}")]
+  Schedule(String),
+}
+
+impl OperatorConfig {
+  /// Layers the other configuration on top, its set fields win.
+  pub fn merge(self, other: Self) -> Self {
+    OperatorConfig {
+      job: merge(self.job, other.job, JobConfig::merge),
+      cancel_timeout_seconds: other
+        .cancel_timeout_seconds
+        .or(self.cancel_timeout_seconds),
+      requeue_seconds: other.requeue_seconds.or(self.requeue_seconds),
+      cache: merge(self.cache, other.cache, CacheDefaults::merge),
+      main_container: merge(
+        self.main_container,
+        other.main_container,
+        |base, other| other.with_defaults(base),
+      ),
+      main_pod: merge(self.main_pod, other.main_pod, |base, other| {
+        other.with_defaults(base)
+      }),
+      delete_legacy_resources: other
+        .delete_legacy_resources
+        .or(self.delete_legacy_resources),
+    }
+  }
+
+  pub fn validate(&self) -> Result<(), OperatorConfigError> {
+    if let Some(job) = &self.job {
+      if job.image.as_deref().is_some_and(str::is_empty) {
+        return Err(OperatorConfigError::Empty("job.image"));
+      }
+      if let Some(schedule) = &job.schedule {
+        if !is_schedule(schedule) {
+          return Err(OperatorConfigError::Schedule(schedule.clone()));
+        }
+      }
+      at_least("job.activeDeadlineSeconds", job.active_deadline_seconds, 1)?;
+      at_least("job.backoffLimit", job.backoff_limit.map(i64::from), 0)?;
+      at_least(
+        "job.successfulJobsHistoryLimit",
+        job.successful_jobs_history_limit.map(i64::from),
+        0,
+      )?;
+      at_least(
+        "job.failedJobsHistoryLimit",
+        job.failed_jobs_history_limit.map(i64::from),
+        0,
+      )?;
+    }
+    if self.cancel_timeout_seconds == Some(0) {
+      return Err(OperatorConfigError::TooSmall("cancelTimeoutSeconds", 1));
+    }
+    if self.requeue_seconds == Some(0) {
+      return Err(OperatorConfigError::TooSmall("requeueSeconds", 1));
+    }
+
+    Ok(())
+  }
+}
+
+impl JobConfig {
+  fn merge(self, other: Self) -> Self {
+    JobConfig {
+      image: other.image.or(self.image),
+      schedule: other.schedule.or(self.schedule),
+      active_deadline_seconds: other
+        .active_deadline_seconds
+        .or(self.active_deadline_seconds),
+      backoff_limit: other.backoff_limit.or(self.backoff_limit),
+      successful_jobs_history_limit: other
+        .successful_jobs_history_limit
+        .or(self.successful_jobs_history_limit),
+      failed_jobs_history_limit: other
+        .failed_jobs_history_limit
+        .or(self.failed_jobs_history_limit),
+      log_level: other.log_level.or(self.log_level),
+      native_sidecars: other.native_sidecars.or(self.native_sidecars),
+    }
+  }
+}
+
+impl CacheDefaults {
+  fn merge(self, other: Self) -> Self {
+    CacheDefaults {
+      claim_name: other.claim_name.or(self.claim_name),
+      max_size: other.max_size.or(self.max_size),
+    }
+  }
+}
+
+fn merge<T>(
+  base: Option<T>,
+  other: Option<T>,
+  merge: impl FnOnce(T, T) -> T,
+) -> Option<T> {
+  match (base, other) {
+    (Some(base), Some(other)) => Some(merge(base, other)),
+    (base, other) => other.or(base),
+  }
+}
+
+// the api server checks the fields, this only catches obvious mistakes early
+fn is_schedule(schedule: &str) -> bool {
+  const MACROS: [&str; 7] = [
+    "@yearly",
+    "@annually",
+    "@monthly",
+    "@weekly",
+    "@daily",
+    "@midnight",
+    "@hourly",
+  ];
+  MACROS.contains(&schedule.trim()) || schedule.split_whitespace().count() == 5
+}
+
+fn at_least(
+  field: &'static str,
+  value: Option<i64>,
+  min: i64,
+) -> Result<(), OperatorConfigError> {
+  match value {
+    Some(value) if value < min => {
+      Err(OperatorConfigError::TooSmall(field, min))
+    }
+    _ => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn job(image: Option<&str>, schedule: Option<&str>) -> Option<JobConfig> {
+    Some(JobConfig {
+      image: image.map(str::to_string),
+      schedule: schedule.map(str::to_string),
+      ..Default::default()
+    })
+  }
+
+  #[test]
+  fn set_fields_of_the_later_config_win() {
+    let file = OperatorConfig {
+      job: job(Some("file:1"), Some("*/5 * * * *")),
+      requeue_seconds: Some(30),
+      cancel_timeout_seconds: Some(600),
+      ..Default::default()
+    };
+    let resource = OperatorConfig {
+      job: job(Some("resource:1"), None),
+      requeue_seconds: Some(60),
+      ..Default::default()
+    };
+
+    let merged = file.merge(resource);
+    assert_eq!(merged.job, job(Some("resource:1"), Some("*/5 * * * *")));
+    assert_eq!(merged.requeue_seconds, Some(60));
+    assert_eq!(merged.cancel_timeout_seconds, Some(600));
+  }
+
+  #[test]
+  fn unset_sections_keep_the_earlier_config() {
+    let file = OperatorConfig {
+      cache: Some(CacheDefaults {
+        claim_name: Some("pulumi-cache".into()),
+        max_size: None,
+      }),
+      delete_legacy_resources: Some(true),
+      ..Default::default()
+    };
+
+    let merged = file.clone().merge(OperatorConfig::default());
+    assert_eq!(merged, file);
+    assert_eq!(OperatorConfig::default().merge(file.clone()), file);
+  }
+
+  #[test]
+  fn valid_configs_pass() {
+    let config = OperatorConfig {
+      job: job(Some("job:1"), Some("@hourly")),
+      requeue_seconds: Some(1),
+      ..Default::default()
+    };
+    assert!(config.validate().is_ok());
+    assert!(OperatorConfig::default().validate().is_ok());
+  }
+
+  #[test]
+  fn invalid_configs_are_rejected() {
+    let invalid = [
+      (job(Some(""), None), "job.image must not be empty"),
+      (
+        job(None, Some("every minute")),
+        "invalid cron schedule \"every minute\"",
+      ),
+      (
+        Some(JobConfig {
+          backoff_limit: Some(-1),
+          ..Default::default()
+        }),
+        "job.backoffLimit must be at least 0",
+      ),
+    ];
+    for (job, message) in invalid {
+      let config = OperatorConfig {
+        job,
+        ..Default::default()
+      };
+      assert_eq!(config.validate().unwrap_err().to_string(), message);
+    }
+
+    let config = OperatorConfig {
+      cancel_timeout_seconds: Some(0),
+      ..Default::default()
+    };
+    assert!(matches!(
+      config.validate(),
+      Err(OperatorConfigError::TooSmall("cancelTimeoutSeconds", 1))
+    ));
+  }
+}
@@ -5,6 +5,74 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "camelCase")]
 pub struct InnerStackAuthSpec {
   pub backend: String,
+  /// Inferred from the backend url when not set.
+  pub backend_type: Option<BackendType>,
   pub backend_auth_secret: Option<String>,
+  /// Maps keys of the backend auth secret to env vars. Defaults to the
+  /// variables the backend type needs.
+  pub backend_env: Option<Vec<BackendEnvVar>>,
   pub access_token_secret: Option<String>,
-}
\ No newline at end of file
+  /// Either `passphrase`, `default` or a provider url like `awskms://...`.
+  /// Existing stacks are migrated when it changes.
+  pub secrets_provider: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+pub enum BackendType {
+  #[serde(rename = "s3")]
+  S3,
+  #[serde(rename = "azblob")]
+  AzureBlob,
+  #[serde(rename = "gs")]
+  GoogleCloudStorage,
+  #[serde(rename = "file")]
+  File,
+  #[serde(rename = "pulumi-cloud")]
+  PulumiCloud,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendEnvVar {
+  pub name: String,
+  /// Secret key to read, defaults to the env var name.
+  pub key: Option<String>,
+  pub optional: Option<bool>,
+}
+
+impl BackendType {
+  pub fn from_url(url: &str) -> Option<Self> {
+    let (scheme, _) = url.split_once("://")?;
+    match scheme {
+      "s3" => Some(BackendType::S3),
+      "azblob" => Some(BackendType::AzureBlob),
+      "gs" => Some(BackendType::GoogleCloudStorage),
+      "file" => Some(BackendType::File),
+      "https" | "http" => Some(BackendType::PulumiCloud),
+      _ => None,
+    }
+  }
+
+  /// Env vars read from the backend auth secret when no mapping is given.
+  pub fn default_env(&self) -> &'static [&'static str] {
+    match self {
+      BackendType::S3 => &[
+        "AWS_ACCESS_KEY_ID",
+        "AWS_DEFAULT_REGION",
+        "AWS_SECRET_ACCESS_KEY",
+      ],
+      BackendType::AzureBlob => &["AZURE_STORAGE_ACCOUNT", "AZURE_STORAGE_KEY"],
+      BackendType::GoogleCloudStorage => &["GOOGLE_CREDENTIALS"],
+      BackendType::File => &[],
+      BackendType::PulumiCloud => &["PULUMI_ACCESS_TOKEN"],
+    }
+  }
+}
+
+impl InnerStackAuthSpec {
+  pub fn backend_type(&self) -> Option<BackendType> {
+    self
+      .backend_type
+      .or_else(|| BackendType::from_url(&self.backend))
+  }
+}
@@ -1,8 +1,10 @@
 use std::sync::Arc;
 
+use crate::operator_config::loader::OperatorConfigLoader;
 use crate::stack::controller_strategy::{
   KubernetesPulumiStackControllerStrategy, PulumiStackControllerStrategyError,
 };
+use crate::stack::sweeper::OrphanSweeper;
 use crate::Inst;
 use springtime::runner::ApplicationRunner;
 use springtime_di::future::{BoxFuture, FutureExt};
@@ -12,13 +14,36 @@ use springtime_di::{component_alias, Component};
 #[derive(Component)]
 pub struct PulumiStackController {
   controller_strategy: Inst<KubernetesPulumiStackControllerStrategy>,
+  orphan_sweeper: Inst<OrphanSweeper>,
+  config_loader: Inst<OperatorConfigLoader>,
 }
 
 impl PulumiStackController {
   async fn run_internal(
     &self,
   ) -> Result<(), PulumiStackControllerStrategyError> {
+    // an invalid configuration fails the startup instead of a later reload
+    self.config_loader.reload().await?;
     self.controller_strategy.initialize().await?;
+    // the sweeper and the config reload never return, they only run alongside
+    // the controller
+    tokio::select! {
+      result = self.update_loop() => result,
+      _ = self.orphan_sweeper.run() => Ok(()),
+      _ = self.config_loop() => Ok(()),
+    }
+  }
+
+  async fn config_loop(&self) {
+    self
+      .config_loader
+      .run(|| async { self.controller_strategy.config_changed() })
+      .await
+  }
+
+  async fn update_loop(
+    &self,
+  ) -> Result<(), PulumiStackControllerStrategyError> {
     loop {
       self.controller_strategy.update().await?;
     }
@@ -1,29 +1,43 @@
+use std::fmt::Debug;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
 
 use crate::Inst;
 use async_trait::async_trait;
-use futures::{Stream, StreamExt};
+use futures::{stream, Stream, StreamExt};
 use kube::core::admission::{
   AdmissionRequest, AdmissionResponse, AdmissionReview,
 };
 use kube::runtime::controller::Action;
-use kube::runtime::reflector::ObjectRef;
+use kube::runtime::reflector::{ObjectRef, Store};
 use kube::runtime::watcher::Config;
-use kube::runtime::{watcher, Controller};
-use kube::Resource;
+use kube::runtime::{
+  predicates, reflector, watcher, Controller, WatchStreamExt,
+};
+use kube::{Api, Resource};
+use serde::de::DeserializeOwned;
 use springtime_di::{component_alias, Component};
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Notify};
 use warp::Filter;
 
+use crate::config_provider::ConfigProvider;
 use crate::kubernetes::service::KubernetesService;
+use crate::operator_config::loader::OperatorConfigLoadError;
 use crate::stack::service::{
   KubernetesPulumiStackService, PulumiStackServiceError,
 };
 
+use super::auth::cluster_crd::ClusterStackAuth;
+use super::auth::crd::StackAuth;
 use super::crd::PulumiStack;
+use super::source::git::cluster_crd::ClusterGitStackSource;
+use super::source::git::crd::GitStackSource;
+use super::source::http::cluster_crd::ClusterHttpStackSource;
+use super::source::http::crd::HttpStackSource;
+use super::source::oci::cluster_crd::ClusterOciStackSource;
+use super::source::oci::crd::OciStackSource;
 
 #[derive(Debug, Error)]
 pub enum PulumiStackControllerStrategyError {
@@ -33,9 +47,12 @@ pub enum PulumiStackControllerStrategyError {
   Service(#[from] PulumiStackServiceError),
   #[error("could not execute controller update")]
   UpdateWatchFailed,
+  #[error("could not load operator configuration")]
+  Config(#[from] OperatorConfigLoadError),
 }
 
 const FINALIZER: &str = "pulumi.stromee.de";
+const DESTROY_POLL_INTERVAL: Duration = Duration::from_secs(15);
 
 type ControllerStream = Pin<
   Box<
@@ -55,17 +72,24 @@ type ControllerStream = Pin<
 pub struct KubernetesPulumiStackControllerStrategy {
   kubernetes_service: Inst<KubernetesService>,
   stack_service: Inst<KubernetesPulumiStackService>,
+  config_provider: Inst<ConfigProvider>,
   #[component(default)]
   controller_stream: Arc<Mutex<Option<ControllerStream>>>,
+  #[component(default)]
+  config_changes: Arc<Notify>,
 }
 
 impl KubernetesPulumiStackControllerStrategy {
+  /// Returns whether the stack can be deleted.
   async fn handle_deletion(
     &self,
     stack: PulumiStack,
-  ) -> Result<(), PulumiStackControllerStrategyError> {
-    self.stack_service.cancel_stack(stack).await?;
-    Ok(())
+  ) -> Result<bool, PulumiStackControllerStrategyError> {
+    self.stack_service.cancel_stack(stack.clone()).await?;
+    if stack.spec.destroy_on_delete.unwrap_or(false) {
+      return Ok(self.stack_service.destroy_stack(stack).await?);
+    }
+    Ok(true)
   }
 
   async fn handle_creation(
@@ -104,9 +128,12 @@ impl KubernetesPulumiStackControllerStrategy {
         .await
         .map_err(Box::from)?;
     } else if stack.meta().deletion_timestamp.is_some() {
-      self
+      let deletable = self
         .handle_deletion(stack.as_ref().clone().try_into().map_err(Box::from)?)
         .await?;
+      if !deletable {
+        return Ok(Action::requeue(DESTROY_POLL_INTERVAL));
+      }
       self
         .kubernetes_service
         .remove_finalizer(stack.as_ref(), FINALIZER)
@@ -126,7 +153,7 @@ impl KubernetesPulumiStackControllerStrategy {
     stack: Arc<PulumiStack>,
     error: &PulumiStackControllerStrategyError,
   ) -> Action {
-    Action::requeue(Duration::from_secs(15))
+    Action::requeue(self.config_provider.requeue_interval())
   }
 }
 
@@ -134,19 +161,93 @@ impl KubernetesPulumiStackControllerStrategy {
   async fn start_controller(
     &self,
   ) -> Result<(), PulumiStackControllerStrategyError> {
-    let controller = Controller::new(
+    let (reader, writer) = reflector::store();
+    // the job patches the status on every run, only spec changes and the
+    // deletion bump the generation. Every stack passes once on startup.
+    let stacks = watcher(
       self
         .kubernetes_service
         .all_in_handled_namespaces_api::<PulumiStack>()
         .await,
       Config::default().any_semantic(),
     )
-    .shutdown_on_signal()
-    .run(
-      |stack, ctx| async move { ctx.reconcile(stack).await },
-      |stack, error, ctx| ctx.handle_error(stack, error),
-      Arc::new(self.clone()),
+    .default_backoff()
+    .reflect(writer)
+    .applied_objects()
+    .predicate_filter(predicates::generation);
+
+    // the generated Role grants the secrets the auth and source reference
+    let kubernetes_service = &self.kubernetes_service;
+    let controller = Controller::for_stream(stacks, reader.clone());
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service
+        .all_in_handled_namespaces_api::<StackAuth>()
+        .await,
+      &reader,
+    );
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service.cluster_api::<ClusterStackAuth>().await,
+      &reader,
+    );
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service
+        .all_in_handled_namespaces_api::<GitStackSource>()
+        .await,
+      &reader,
+    );
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service
+        .cluster_api::<ClusterGitStackSource>()
+        .await,
+      &reader,
     );
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service
+        .all_in_handled_namespaces_api::<OciStackSource>()
+        .await,
+      &reader,
+    );
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service
+        .cluster_api::<ClusterOciStackSource>()
+        .await,
+      &reader,
+    );
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service
+        .all_in_handled_namespaces_api::<HttpStackSource>()
+        .await,
+      &reader,
+    );
+    let controller = watches_referenced(
+      controller,
+      kubernetes_service
+        .cluster_api::<ClusterHttpStackSource>()
+        .await,
+      &reader,
+    );
+
+    let config_changes =
+      stream::unfold(self.config_changes.clone(), |changes| async move {
+        changes.notified().await;
+        Some(((), changes))
+      });
+
+    let controller = controller
+      .reconcile_all_on(config_changes)
+      .shutdown_on_signal()
+      .run(
+        |stack, ctx| async move { ctx.reconcile(stack).await },
+        |stack, error, ctx| ctx.handle_error(stack, error),
+        Arc::new(self.clone()),
+      );
 
     // self.start_admission_controller().await?;
 
@@ -195,6 +296,12 @@ impl KubernetesPulumiStackControllerStrategy {
 }
 
 impl KubernetesPulumiStackControllerStrategy {
+  /// Reconciles every stack again after the operator configuration changed.
+  /// Jobs only restart if their rendered spec changed.
+  pub fn config_changed(&self) {
+    self.config_changes.notify_one();
+  }
+
   pub async fn initialize(
     &self,
   ) -> Result<(), PulumiStackControllerStrategyError> {
@@ -217,6 +324,34 @@ impl KubernetesPulumiStackControllerStrategy {
   }
 }
 
+/// Reconciles the stacks using an auth or source when it changes. Namespaced
+/// objects are only used by stacks of their namespace.
+fn watches_referenced<K>(
+  controller: Controller<PulumiStack>,
+  api: Api<K>,
+  stacks: &Store<PulumiStack>,
+) -> Controller<PulumiStack>
+where
+  K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send,
+  K: 'static,
+{
+  let stacks = stacks.clone();
+  controller.watches(api, Config::default().any_semantic(), move |object| {
+    let kind = K::kind(&());
+    let name = object.meta().name.clone().unwrap_or_default();
+    let namespace = object.meta().namespace.clone();
+    stacks
+      .state()
+      .into_iter()
+      .filter(|stack| {
+        namespace.is_none() || stack.metadata.namespace == namespace
+      })
+      .filter(|stack| stack.references(&kind, &name))
+      .map(|stack| ObjectRef::from_obj(stack.as_ref()))
+      .collect::<Vec<_>>()
+  })
+}
+
 #[derive(Debug, Error)]
 pub enum PulumiStackConversionError {
   #[error("name is empty")]
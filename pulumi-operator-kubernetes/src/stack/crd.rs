@@ -1,9 +1,15 @@
-use k8s_openapi::api::core::v1::{Container, EnvVar, Volume, VolumeMount};
+use k8s_openapi::api::core::v1::{
+  Affinity, Container, EnvVar, LocalObjectReference, PodSecurityContext,
+  ResourceRequirements, SecurityContext, Toleration, Volume, VolumeMount,
+};
+use k8s_openapi::api::rbac::v1::PolicyRule;
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
 use k8s_openapi::schemars::JsonSchema;
 use kube::CustomResource;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
+use super::source::inline::InlineProgram;
 use super::status::StackStatus;
 
 #[derive(CustomResource, Serialize, Deserialize, Clone, Debug, JsonSchema)]
@@ -18,27 +24,196 @@ use super::status::StackStatus;
 #[serde(rename_all = "camelCase")]
 pub struct StackSpec {
   pub stack_name: Option<String>,
-  pub source: StackSourceRef,
+  pub source: Option<StackSourceRef>,
+  pub inline: Option<InlineProgram>,
   pub auth: StackAuthRef,
   pub path: Option<String>,
+  /// Skips the run of a new revision that changes none of these paths since
+  /// the last deployed revision. Changes to the stack itself always deploy.
+  pub watch_paths: Option<Vec<String>>,
   pub init_containers: Option<Vec<Container>>,
   pub extra_volumes: Option<Vec<Volume>>,
   pub main_container: Option<MainContainerOverride>,
   pub main_pod: Option<MainPodOverride>,
   pub organization: Option<String>,
+  pub cache: Option<CacheSpec>,
+  pub hooks: Option<StackHooks>,
+  /// Runs `pulumi destroy` in a one-off job when the stack is deleted, the
+  /// deletion waits for it to succeed.
+  pub destroy_on_delete: Option<bool>,
+  pub service_account: Option<ServiceAccountSpec>,
+  pub rbac: Option<RbacSpec>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RbacSpec {
+  /// Added to the generated Role of the stack job.
+  pub extra_rules: Option<Vec<PolicyRule>>,
+  /// ClusterRoles bound to the stack job in the stack namespace.
+  pub cluster_roles: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceAccountSpec {
+  /// Existing ServiceAccount to run the job as instead of a generated one.
+  pub name: Option<String>,
+  pub annotations: Option<BTreeMap<String, String>>,
+  pub labels: Option<BTreeMap<String, String>>,
+  pub automount_service_account_token: Option<bool>,
+}
+
+/// Commands run in the program directory around pulumi operations.
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StackHooks {
+  pub pre_up: Option<Vec<StackHook>>,
+  pub post_up: Option<Vec<StackHook>>,
+  /// Run before the stack is destroyed, see `destroyOnDelete`.
+  pub pre_destroy: Option<Vec<StackHook>>,
+  /// Run after the stack is destroyed, with the outputs it had before.
+  pub post_destroy: Option<Vec<StackHook>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StackHook {
+  pub command: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
+pub struct CacheSpec {
+  pub claim_name: Option<String>,
+  pub max_size: Option<Quantity>,
+  pub disabled: Option<bool>,
+}
+
+#[derive(
+  Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
 pub struct MainContainerOverride {
+  pub image: Option<String>,
+  pub image_pull_policy: Option<String>,
+  pub resources: Option<ResourceRequirements>,
+  pub security_context: Option<SecurityContext>,
   pub extra_volume_mounts: Option<Vec<VolumeMount>>,
   pub extra_env: Option<Vec<EnvVar>>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[derive(
+  Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema,
+)]
 #[serde(rename_all = "camelCase")]
 pub struct MainPodOverride {
   pub extra_annotations: Option<BTreeMap<String, String>>,
+  pub extra_labels: Option<BTreeMap<String, String>>,
+  pub node_selector: Option<BTreeMap<String, String>>,
+  pub tolerations: Option<Vec<Toleration>>,
+  pub affinity: Option<Affinity>,
+  pub priority_class_name: Option<String>,
+  pub security_context: Option<PodSecurityContext>,
+  pub image_pull_secrets: Option<Vec<LocalObjectReference>>,
+  /// Sidecars running next to the pulumi container. They are rendered as
+  /// native sidecars when the operator enables `job.nativeSidecars`,
+  /// otherwise they have to exit on their own for the job to complete.
+  pub extra_containers: Option<Vec<Container>>,
+}
+
+impl MainContainerOverride {
+  /// Fills unset fields from the defaults, lists of the defaults go first.
+  pub fn with_defaults(self, defaults: Self) -> Self {
+    MainContainerOverride {
+      image: self.image.or(defaults.image),
+      image_pull_policy: self.image_pull_policy.or(defaults.image_pull_policy),
+      resources: self.resources.or(defaults.resources),
+      security_context: self.security_context.or(defaults.security_context),
+      extra_volume_mounts: concat(
+        defaults.extra_volume_mounts,
+        self.extra_volume_mounts,
+      ),
+      extra_env: concat(defaults.extra_env, self.extra_env),
+    }
+  }
+}
+
+impl MainPodOverride {
+  /// Fills unset fields from the defaults, lists of the defaults go first and
+  /// map entries of the stack win.
+  pub fn with_defaults(self, defaults: Self) -> Self {
+    MainPodOverride {
+      extra_annotations: extend(
+        defaults.extra_annotations,
+        self.extra_annotations,
+      ),
+      extra_labels: extend(defaults.extra_labels, self.extra_labels),
+      node_selector: self.node_selector.or(defaults.node_selector),
+      tolerations: self.tolerations.or(defaults.tolerations),
+      affinity: self.affinity.or(defaults.affinity),
+      priority_class_name: self
+        .priority_class_name
+        .or(defaults.priority_class_name),
+      security_context: self.security_context.or(defaults.security_context),
+      image_pull_secrets: self
+        .image_pull_secrets
+        .or(defaults.image_pull_secrets),
+      extra_containers: concat(
+        defaults.extra_containers,
+        self.extra_containers,
+      ),
+    }
+  }
+}
+
+fn concat<T>(first: Option<Vec<T>>, second: Option<Vec<T>>) -> Option<Vec<T>> {
+  match (first, second) {
+    (Some(mut first), Some(second)) => {
+      first.extend(second);
+      Some(first)
+    }
+    (first, second) => first.or(second),
+  }
+}
+
+fn extend<K: Ord, V>(
+  first: Option<BTreeMap<K, V>>,
+  second: Option<BTreeMap<K, V>>,
+) -> Option<BTreeMap<K, V>> {
+  match (first, second) {
+    (Some(mut first), Some(second)) => {
+      first.extend(second);
+      Some(first)
+    }
+    (first, second) => first.or(second),
+  }
+}
+
+impl PulumiStack {
+  /// The ServiceAccount the stack job runs as.
+  pub fn service_account_name(&self) -> Option<String> {
+    self
+      .spec
+      .service_account
+      .as_ref()
+      .and_then(|service_account| service_account.name.clone())
+      .or_else(|| self.metadata.name.clone())
+  }
+
+  /// Whether the stack uses the auth or source of the kind and name.
+  pub fn references(&self, kind: &str, name: &str) -> bool {
+    let auth = &self.spec.auth;
+    let source = self
+      .spec
+      .source
+      .as_ref()
+      .filter(|_| self.spec.inline.is_none());
+    (auth.type_.kind() == kind && auth.name == name)
+      || source.is_some_and(|source| {
+        source.type_.kind() == kind && source.name == name
+      })
+  }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
@@ -58,6 +233,15 @@ pub enum StackAuthRefType {
   Cluster,
 }
 
+impl StackAuthRefType {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      StackAuthRefType::Namespace => "StackAuth",
+      StackAuthRefType::Cluster => "ClusterStackAuth",
+    }
+  }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct StackSourceRef {
@@ -77,4 +261,21 @@ pub enum StackSourceRefType {
   Oci,
   #[serde(rename = "ClusterOciStackSource")]
   ClusterOci,
+  #[serde(rename = "HttpStackSource")]
+  Http,
+  #[serde(rename = "ClusterHttpStackSource")]
+  ClusterHttp,
+}
+
+impl StackSourceRefType {
+  pub fn kind(&self) -> &'static str {
+    match self {
+      StackSourceRefType::Git => "GitStackSource",
+      StackSourceRefType::ClusterGit => "ClusterGitStackSource",
+      StackSourceRefType::Oci => "OciStackSource",
+      StackSourceRefType::ClusterOci => "ClusterOciStackSource",
+      StackSourceRefType::Http => "HttpStackSource",
+      StackSourceRefType::ClusterHttp => "ClusterHttpStackSource",
+    }
+  }
 }
@@ -1,38 +1,69 @@
 use async_trait::async_trait;
-use futures::{StreamExt, TryStreamExt};
 use k8s_openapi::api::batch::v1::{CronJob, Job};
-use k8s_openapi::api::core::v1::{Container, ServiceAccount};
+use k8s_openapi::api::core::v1::{
+  Container, EnvVar, ServiceAccount, Volume, VolumeMount,
+};
 use k8s_openapi::api::rbac::v1::{Role, RoleBinding};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
-use kube::api::{DeleteParams, Object, PostParams, WatchEvent};
+use kube::api::{DeleteParams, ListParams, Object, Patch, PatchParams};
+use kube::{Api, Resource};
+use serde::de::DeserializeOwned;
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use springtime_di::{component_alias, Component};
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
+use std::fmt::Debug;
 use std::time::Duration;
 use thiserror::Error;
-use tokio::time::timeout;
+use tokio::time::Instant;
 
 use crate::config_provider::ConfigProvider;
 use crate::kubernetes::service::KubernetesService;
-use crate::stack::crd::PulumiStack;
+use crate::stack::auth::repository::StackAuthRepository;
+use crate::stack::crd::{
+  CacheSpec, PulumiStack, StackAuthRefType, StackSourceRef, StackSourceRefType,
+};
+use crate::stack::source::git::repository::GitStackSourceRepository;
+use crate::stack::source::http::repository::HttpStackSourceRepository;
+use crate::stack::source::oci::repository::OciStackSourceRepository;
+use crate::stack::source::Source;
 use crate::Inst;
 
 #[derive(Debug, Error)]
 pub enum PulumiStackServiceError {
-  #[error("pulumi task cancellation failed")]
-  CancelFailed,
+  #[error("pulumi task cancellation failed: {0}")]
+  CancelFailed(Box<dyn Error + Sync + Send>),
 
   #[error("Configuration error: {0}")]
   Config(Box<dyn Error + Sync + Send>),
 
   #[error("pulumi stack update failed: {0}")]
   UpdateFailed(Box<dyn Error + Sync + Send>),
+
+  #[error("pulumi stack destroy failed: {0}")]
+  DestroyFailed(Box<dyn Error + Sync + Send>),
 }
 
+const STACK_LABEL: &str = "pulumi.stromee.de/stack";
+pub(crate) const MANAGED_BY_LABEL: &str = "app.kubernetes.io/managed-by";
+pub(crate) const MANAGED_BY: &str = "pulumi-operator";
+const CLUSTER_ROLE_BINDING_LABEL: &str =
+  "pulumi.stromee.de/cluster-role-binding";
+const SPEC_HASH_ANNOTATION: &str = "pulumi.stromee.de/spec-hash";
+const DELETE_TIMEOUT: Duration = Duration::from_secs(60);
+const DELETE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const CACHE_VOLUME: &str = "pulumi-cache";
+const CACHE_MOUNT_PATH: &str = "/cache";
+
 #[derive(Component)]
 pub struct KubernetesPulumiStackService {
   kubernetes_service: Inst<KubernetesService>,
   config_provider: Inst<ConfigProvider>,
+  stack_auth_repository: Inst<StackAuthRepository>,
+  git_stack_source_repository: Inst<GitStackSourceRepository>,
+  oci_stack_source_repository: Inst<OciStackSourceRepository>,
+  http_stack_source_repository: Inst<HttpStackSourceRepository>,
 }
 
 impl KubernetesPulumiStackService {
@@ -40,36 +71,119 @@ impl KubernetesPulumiStackService {
     &self,
     stack: PulumiStack,
   ) -> Result<(), PulumiStackServiceError> {
-    self.cancel_stack(stack.clone()).await?;
     self.create_service_account(stack.clone()).await?;
     self.create_role(stack.clone()).await?;
     self.create_role_binding(stack.clone()).await?;
+    self.create_cluster_role_bindings(stack.clone()).await?;
+
+    let mut job = self.render_job(stack.clone())?;
+    let spec_hash = hex::encode(Sha256::digest(
+      serde_json::to_vec(&job)
+        .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?,
+    ));
+    job
+      .metadata
+      .annotations
+      .get_or_insert_with(BTreeMap::new)
+      .insert(SPEC_HASH_ANNOTATION.into(), spec_hash.clone());
+
+    let current = self
+      .kubernetes_service
+      .all_in_namespace_api::<CronJob>(
+        stack.metadata.namespace.clone().unwrap(),
+      )
+      .await
+      .get_opt(job.metadata.name.as_ref().unwrap())
+      .await
+      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    // a cron job left suspended by an aborted cancellation is recreated
+    let current_hash = current
+      .as_ref()
+      .filter(|current| {
+        !current
+          .spec
+          .as_ref()
+          .and_then(|spec| spec.suspend)
+          .unwrap_or(false)
+      })
+      .and_then(|current| current.metadata.annotations.as_ref())
+      .and_then(|annotations| annotations.get(SPEC_HASH_ANNOTATION));
+
+    match current_hash {
+      Some(current_hash) if *current_hash == spec_hash => {
+        tracing::debug!("job of {} is up to date", job.metadata.name.unwrap());
+        return Ok(());
+      }
+      // only interrupt a running job when it would run something else
+      _ if current.is_some() => self.cancel_stack(stack).await?,
+      _ => {}
+    }
 
-    let name = stack.metadata.name.unwrap();
+    self
+      .kubernetes_service
+      .apply(&job)
+      .await
+      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+
+    Ok(())
+  }
+
+  fn render_job(
+    &self,
+    stack: PulumiStack,
+  ) -> Result<CronJob, PulumiStackServiceError> {
+    let service_account_name = stack.service_account_name().unwrap();
+    let name = stack.metadata.name.clone().unwrap();
+    let metadata = generated_metadata(&stack, format!("pulumi-{}", name));
     let namespace = stack.metadata.namespace.unwrap();
-    let init_containers = stack.spec.init_containers;
-    let extra_volumes = stack.spec.extra_volumes;
-    let container_override = stack.spec.main_container;
-    let pod_override = stack.spec.main_pod;
+    let mut init_containers = stack.spec.init_containers.unwrap_or_default();
+    let mut volumes = stack.spec.extra_volumes.unwrap_or_default();
+    let container_override = stack.spec.main_container.unwrap_or_default();
+    let pod_override = stack.spec.main_pod.unwrap_or_default();
+    let cache = self.cache_settings(stack.spec.cache.as_ref());
+    let cancel_timeout = self.config_provider.cancel_timeout();
+    let job_image = self.config_provider.job_image();
+    let successful_jobs_history_limit =
+      self.config_provider.successful_jobs_history_limit();
+    let failed_jobs_history_limit =
+      self.config_provider.failed_jobs_history_limit();
 
     let operator_namespace = self
       .config_provider
       .operator_namespace()
       .map_err(|e| PulumiStackServiceError::Config(Box::new(e)))?; // TODO
+    let container_override = container_override.with_defaults(
+      self
+        .config_provider
+        .default_main_container()
+        .map_err(|e| PulumiStackServiceError::Config(Box::new(e)))?,
+    );
+    let pod_override = pod_override.with_defaults(
+      self
+        .config_provider
+        .default_main_pod()
+        .map_err(|e| PulumiStackServiceError::Config(Box::new(e)))?,
+    );
+
+    let resources = match container_override.resources {
+      Some(resources) => json!(resources),
+      None => json!({
+          "requests": {
+              "cpu": "100m",
+              "memory": "500Mi",
+          },
+          "limits": {
+              "cpu": "300m",
+              "memory": "750Mi"
+          },
+      }),
+    };
 
     let mut main_container: Container = serde_json::from_value(json!({
         "name": "pulumi",
-        "image": "ghcr.io/stromee/pulumi-operator/pulumi-operator-kubernetes-job:1.0.31",
-        "resources": {
-            "requests": {
-                "cpu": "100m",
-                "memory": "500Mi",
-            },
-            "limits": {
-                "cpu": "300m",
-                "memory": "750Mi"
-            },
-        },
+        "image": container_override.image.unwrap_or(job_image),
+        "resources": resources,
+        "securityContext": container_override.security_context,
         "env": [{
             "name": "PULUMI_STACK",
             "value": name
@@ -79,122 +193,188 @@ impl KubernetesPulumiStackService {
         }, {
             "name": ConfigProvider::OPERATOR_NS_VAR,
             "value": operator_namespace
+        }, {
+            "name": ConfigProvider::CANCEL_TIMEOUT_VAR,
+            "value": cancel_timeout.as_secs().to_string()
         }, {
             "name": "RUST_BACKTRACE",
             "value": "full"
         }, {
             "name": "RUST_LOG",
-            "value": "trace"
+            "value": self.config_provider.job_log_level()
         }],
-        "imagePullPolicy": "Always"
-    })).unwrap();
+        "imagePullPolicy": container_override
+            .image_pull_policy
+            .as_deref()
+            .unwrap_or("Always")
+    }))
+    .unwrap();
 
-    if let Some(main_container_override) = container_override {
-      if let Some(mut extra_volume_mounts) =
-        main_container_override.extra_volume_mounts
-      {
-        let mut volume_mounts =
-          main_container.volume_mounts.clone().unwrap_or_default();
-        volume_mounts.append(&mut extra_volume_mounts);
-        main_container.volume_mounts.replace(volume_mounts);
-      }
+    if let Some(mut extra_volume_mounts) =
+      container_override.extra_volume_mounts
+    {
+      let mut volume_mounts =
+        main_container.volume_mounts.clone().unwrap_or_default();
+      volume_mounts.append(&mut extra_volume_mounts);
+      main_container.volume_mounts.replace(volume_mounts);
+    }
 
-      if let Some(mut extra_env) = main_container_override.extra_env {
-        let mut env = main_container.env.clone().unwrap_or_default();
-        env.append(&mut extra_env);
-        main_container.env.replace(env);
+    if let Some(mut extra_env) = container_override.extra_env {
+      let mut env = main_container.env.clone().unwrap_or_default();
+      env.append(&mut extra_env);
+      main_container.env.replace(env);
+    }
+
+    if let Some((claim_name, max_size)) = cache {
+      volumes.push(
+        serde_json::from_value::<Volume>(json!({
+            "name": CACHE_VOLUME,
+            "persistentVolumeClaim": {
+                "claimName": claim_name
+            }
+        }))
+        .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?,
+      );
+      main_container
+        .volume_mounts
+        .get_or_insert_with(Vec::new)
+        .push(VolumeMount {
+          name: CACHE_VOLUME.into(),
+          mount_path: CACHE_MOUNT_PATH.into(),
+          ..Default::default()
+        });
+
+      let env = main_container.env.get_or_insert_with(Vec::new);
+      env.push(EnvVar {
+        name: ConfigProvider::CACHE_DIR_VAR.into(),
+        value: Some(CACHE_MOUNT_PATH.into()),
+        ..Default::default()
+      });
+      if let Some(max_size) = max_size {
+        env.push(EnvVar {
+          name: ConfigProvider::CACHE_MAX_SIZE_VAR.into(),
+          value: Some(max_size),
+          ..Default::default()
+        });
       }
     }
 
-    let pod_annotations = pod_override
-      .as_ref()
-      .and_then(|pod| pod.extra_annotations.clone());
+    // regular sidecars keep the job pod running after pulumi exits, native
+    // sidecars are stopped with it but need a recent cluster
+    let extra_containers = pod_override.extra_containers.unwrap_or_default();
+    let mut containers = vec![main_container];
+    if self.config_provider.native_sidecars() {
+      init_containers.extend(extra_containers.into_iter().map(|container| {
+        Container {
+          restart_policy: Some("Always".into()),
+          ..container
+        }
+      }));
+    } else {
+      containers.extend(extra_containers);
+    }
 
-    let job = serde_json::from_value(json!({
+    serde_json::from_value(json!({
         "apiVersion": "batch/v1",
         "kind": "CronJob",
-        "metadata": {
-            "name": format!("pulumi-{}", name),
-            "namespace": namespace.clone()
-        },
+        "metadata": metadata,
         "spec": {
-            "schedule": "* * * * *",
+            "schedule": self.config_provider.job_schedule(),
             "concurrencyPolicy": "Forbid",
             "jobTemplate": {
                 "spec": {
-                    "activeDeadlineSeconds": 1 * 60 * 60, // 1 hour timeout
-                    "backoffLimit": 10000,
+                    "activeDeadlineSeconds": self
+                        .config_provider
+                        .job_active_deadline_seconds(),
+                    "backoffLimit": self.config_provider.job_backoff_limit(),
                     "template": {
                         "metadata": {
                             "name": "pulumi",
-                            "annotations": pod_annotations
+                            "annotations": pod_override.extra_annotations,
+                            "labels": pod_override.extra_labels
                         },
                         "spec": {
                             "initContainers": init_containers,
-                            "containers": [main_container],
-                            "volumes": extra_volumes,
-                            "serviceAccountName": &name,
-                            "restartPolicy": "Never"
+                            "containers": containers,
+                            "volumes": volumes,
+                            "serviceAccountName": service_account_name,
+                            "nodeSelector": pod_override.node_selector,
+                            "tolerations": pod_override.tolerations,
+                            "affinity": pod_override.affinity,
+                            "priorityClassName": pod_override.priority_class_name,
+                            "securityContext": pod_override.security_context,
+                            "imagePullSecrets": pod_override.image_pull_secrets,
+                            "restartPolicy": "Never",
+                            "terminationGracePeriodSeconds": cancel_timeout.as_secs()
                         }
                     },
-                    "successfulJobsHistoryLimit": 1,
-                    "failedJobsHistoryLimit": 1
+                    "successfulJobsHistoryLimit": successful_jobs_history_limit,
+                    "failedJobsHistoryLimit": failed_jobs_history_limit
                 },
             },
-            "successfulJobsHistoryLimit": 1,
-            "failedJobsHistoryLimit": 1
+            "successfulJobsHistoryLimit": successful_jobs_history_limit,
+            "failedJobsHistoryLimit": failed_jobs_history_limit
         }
     }))
-    .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))
+  }
 
-    let api = self
-      .kubernetes_service
-      .all_in_namespace_api::<CronJob>(namespace.clone())
-      .await;
+  /// Resolves the cache claim and size, falling back to the operator defaults.
+  fn cache_settings(
+    &self,
+    cache: Option<&CacheSpec>,
+  ) -> Option<(String, Option<String>)> {
+    if cache.and_then(|cache| cache.disabled).unwrap_or(false) {
+      return None;
+    }
 
-    api
-      .create(&PostParams::default(), &job)
-      .await
-      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    let claim_name = cache
+      .and_then(|cache| cache.claim_name.clone())
+      .or_else(|| self.config_provider.default_cache_claim_name())?;
+    let max_size = cache
+      .and_then(|cache| cache.max_size.as_ref())
+      .map(|max_size| max_size.0.clone())
+      .or_else(|| self.config_provider.default_cache_max_size());
 
-    Ok(())
+    Some((claim_name, max_size))
   }
 
   async fn create_service_account(
     &self,
     stack: PulumiStack,
   ) -> Result<(), PulumiStackServiceError> {
-    let namespace = stack.metadata.namespace.unwrap();
     let name = stack.metadata.name.clone().unwrap();
+    let mut metadata = generated_metadata(&stack, &name);
+    let spec = stack.spec.service_account.unwrap_or_default();
+
+    if let Some(existing) = &spec.name {
+      if spec.annotations.is_some() || spec.labels.is_some() {
+        tracing::warn!(
+          "serviceAccount {} is not generated, ignoring annotations and labels",
+          existing
+        );
+      }
+      return Ok(());
+    }
+
+    let mut labels = spec.labels.unwrap_or_default();
+    labels.extend(metadata.labels.take().unwrap_or_default());
+    metadata.labels = Some(labels);
+    metadata.annotations = spec.annotations;
 
     let service_account: ServiceAccount = serde_json::from_value(json!({
         "apiVersion": "v1",
         "kind": "ServiceAccount",
-        "metadata": {
-            "name": &name,
-            "namespace": &namespace
-        }
+        "metadata": metadata,
+        "automountServiceAccountToken": spec.automount_service_account_token
     }))
-    .unwrap();
+    .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
 
-    let api = self
+    self
       .kubernetes_service
-      .all_in_namespace_api(&namespace)
-      .await;
-    match api.get(&name).await {
-      Ok(_) => {
-        api
-          .replace(&name, &PostParams::default(), &service_account)
-          .await
-          .unwrap();
-      }
-      Err(_) => {
-        api
-          .create(&PostParams::default(), &service_account)
-          .await
-          .unwrap();
-      }
-    }
+      .apply(&service_account)
+      .await
+      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
     Ok(())
   }
 
@@ -202,60 +382,191 @@ impl KubernetesPulumiStackService {
     &self,
     stack: PulumiStack,
   ) -> Result<(), PulumiStackServiceError> {
-    let namespace = stack.metadata.namespace.unwrap();
     let name = stack.metadata.name.clone().unwrap();
-
-    let role: Role = serde_json::from_value(json!({
+    let metadata = generated_metadata(&stack, &name);
+
+    let (secrets, config_maps) = self.referenced_objects(&stack).await?;
+    let extra_rules = stack
+      .spec
+      .rbac
+      .and_then(|rbac| rbac.extra_rules)
+      .unwrap_or_default();
+
+    // only what the job reads itself, programs managing kubernetes resources
+    // need extraRules or clusterRoles
+    let mut role: Role = serde_json::from_value(json!({
         "apiVersion": "rbac.authorization.k8s.io/v1",
         "kind": "Role",
-        "metadata": {
-            "name": name,
-            "namespace": namespace
-        },
+        "metadata": metadata,
         "rules": [{
-            "apiGroups": ["*"],
-            "resources": ["*"],
-            "verbs": ["*"]
+            "apiGroups": ["pulumi.stromee.de"],
+            "resources": [
+                "pulumistacks",
+                "stackauths",
+                "gitstacksources",
+                "ocistacksources",
+                "httpstacksources"
+            ],
+            "verbs": ["get"]
+        }, {
+            "apiGroups": ["pulumi.stromee.de"],
+            "resources": ["pulumistacks/status"],
+            "verbs": ["get", "patch", "update"]
         }]
     }))
-    .unwrap();
+    .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    let rules = role.rules.get_or_insert_with(Vec::new);
+    // an empty resourceNames list would grant every object
+    for (resource, names) in [("secrets", secrets), ("configmaps", config_maps)]
+    {
+      if !names.is_empty() {
+        rules.push(
+          serde_json::from_value(json!({
+              "apiGroups": [""],
+              "resources": [resource],
+              "resourceNames": names,
+              "verbs": ["get"]
+          }))
+          .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?,
+        );
+      }
+    }
+    rules.extend(extra_rules);
 
-    let api = self
+    self
       .kubernetes_service
-      .all_in_namespace_api(&namespace)
-      .await;
+      .apply(&role)
+      .await
+      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    Ok(())
+  }
 
-    match api.get(&name).await {
-      Ok(_) => {
-        api
-          .replace(&name, &PostParams::default(), &role)
+  /// Names of the secrets and config maps the job reads for the stack. Fails
+  /// until the referenced auth and source exist, so the stack is requeued.
+  async fn referenced_objects(
+    &self,
+    stack: &PulumiStack,
+  ) -> Result<(BTreeSet<String>, BTreeSet<String>), PulumiStackServiceError> {
+    // the job looks up every secret and config map by name in the namespace
+    // of the stack, also those of cluster scoped auths and sources, so the
+    // namespaced Role covers all of them
+    let namespace = stack.metadata.namespace.clone().unwrap();
+    let mut secrets = BTreeSet::new();
+    let mut config_maps = BTreeSet::new();
+
+    let auth_ref = &stack.spec.auth;
+    let auth = match auth_ref.type_ {
+      StackAuthRefType::Namespace => self
+        .stack_auth_repository
+        .get_namespaced_by_name_and_namespace(&auth_ref.name, &namespace)
+        .await
+        .map(|auth| auth.spec.inner),
+      StackAuthRefType::Cluster => self
+        .stack_auth_repository
+        .get_by_name(&auth_ref.name)
+        .await
+        .map(|auth| auth.spec.inner),
+    }
+    .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    secrets.extend(auth.backend_auth_secret);
+    secrets.extend(auth.access_token_secret);
+
+    let source = match (&stack.spec.inline, &stack.spec.source) {
+      (Some(program), _) => Some(program.clone().into()),
+      (None, Some(source_ref)) => Some(
+        self
+          .source(&namespace, source_ref)
           .await
-          .unwrap();
+          .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?,
+      ),
+      (None, None) => None,
+    };
+    match source {
+      Some(Source::Git(spec)) => {
+        secrets.extend(spec.auth.and_then(|auth| auth.secret_ref));
+        secrets.extend(spec.verify.map(|verify| verify.public_keys_secret_ref));
+        secrets.extend(spec.ca_bundle_secret_ref);
       }
-      Err(_) => {
-        api.create(&PostParams::default(), &role).await.unwrap();
+      Some(Source::Oci(spec)) => {
+        secrets.extend(spec.verify.map(|verify| verify.public_key_secret_ref))
       }
+      Some(Source::Http(spec)) => secrets.extend(spec.headers_secret_ref),
+      Some(Source::Inline(program)) => config_maps
+        .extend(program.config_map_ref.map(|config_map| config_map.name)),
+      None => {}
     }
-    Ok(())
+
+    Ok((secrets, config_maps))
+  }
+
+  async fn source(
+    &self,
+    namespace: &str,
+    source_ref: &StackSourceRef,
+  ) -> Result<Source, kube::Error> {
+    let name = &source_ref.name;
+    Ok(match source_ref.type_ {
+      StackSourceRefType::Git => self
+        .git_stack_source_repository
+        .get_namespaced_by_name_and_namespace(name, namespace)
+        .await?
+        .spec
+        .inner
+        .into(),
+      StackSourceRefType::ClusterGit => self
+        .git_stack_source_repository
+        .get_by_name(name)
+        .await?
+        .spec
+        .inner
+        .into(),
+      StackSourceRefType::Oci => self
+        .oci_stack_source_repository
+        .get_namespaced_by_name_and_namespace(name, namespace)
+        .await?
+        .spec
+        .inner
+        .into(),
+      StackSourceRefType::ClusterOci => self
+        .oci_stack_source_repository
+        .get_by_name(name)
+        .await?
+        .spec
+        .inner
+        .into(),
+      StackSourceRefType::Http => self
+        .http_stack_source_repository
+        .get_namespaced_by_name_and_namespace(name, namespace)
+        .await?
+        .spec
+        .inner
+        .into(),
+      StackSourceRefType::ClusterHttp => self
+        .http_stack_source_repository
+        .get_by_name(name)
+        .await?
+        .spec
+        .inner
+        .into(),
+    })
   }
 
   async fn create_role_binding(
     &self,
     stack: PulumiStack,
   ) -> Result<(), PulumiStackServiceError> {
-    let namespace = stack.metadata.namespace.unwrap();
+    let service_account_name = stack.service_account_name().unwrap();
+    let namespace = stack.metadata.namespace.clone().unwrap();
     let name = stack.metadata.name.clone().unwrap();
+    let metadata = generated_metadata(&stack, &name);
 
     let role_binding: RoleBinding = serde_json::from_value(json!({
         "apiVersion": "rbac.authorization.k8s.io/v1",
         "kind": "RoleBinding",
-        "metadata": {
-            "name": name,
-            "namespace": namespace
-        },
+        "metadata": metadata,
         "subjects": [{
             "kind": "ServiceAccount",
-            "name": name,
+            "name": service_account_name,
             "namespace": namespace
         }],
         "roleRef": {
@@ -264,79 +575,364 @@ impl KubernetesPulumiStackService {
             "name": name
         }
     }))
-    .unwrap();
+    .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+
+    self
+      .kubernetes_service
+      .apply(&role_binding)
+      .await
+      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+
+    Ok(())
+  }
+
+  async fn create_cluster_role_bindings(
+    &self,
+    stack: PulumiStack,
+  ) -> Result<(), PulumiStackServiceError> {
+    let service_account_name = stack.service_account_name().unwrap();
+    let namespace = stack.metadata.namespace.clone().unwrap();
+    let name = stack.metadata.name.clone().unwrap();
+    let cluster_roles = stack
+      .spec
+      .rbac
+      .clone()
+      .and_then(|rbac| rbac.cluster_roles)
+      .unwrap_or_default();
 
     let api = self
       .kubernetes_service
-      .all_in_namespace_api(&namespace)
+      .all_in_namespace_api::<RoleBinding>(&namespace)
       .await;
-
-    match api.get(&name).await {
-      Ok(_) => {
-        api
-          .replace(&name, &PostParams::default(), &role_binding)
-          .await
-          .unwrap();
+    let binding_name =
+      |cluster_role: &str| format!("{}-{}", name, cluster_role);
+
+    // drop bindings of cluster roles that were removed from the spec
+    let existing = api
+      .list(&ListParams::default().labels(&format!(
+        "{}={},{}",
+        STACK_LABEL,
+        stack_label_value(&name),
+        CLUSTER_ROLE_BINDING_LABEL
+      )))
+      .await
+      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    for binding in existing {
+      // shortened label values may be shared, the owner is exact
+      if owner_uid(&binding.metadata) != stack.metadata.uid.as_deref() {
+        continue;
       }
-      Err(_) => {
+      let Some(existing_name) = binding.metadata.name else {
+        continue;
+      };
+      if !cluster_roles
+        .iter()
+        .any(|cluster_role| existing_name == binding_name(cluster_role))
+      {
         api
-          .create(&PostParams::default(), &role_binding)
+          .delete(&existing_name, &DeleteParams::default())
           .await
-          .unwrap();
+          .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
       }
     }
 
+    for cluster_role in &cluster_roles {
+      let binding_name = binding_name(cluster_role);
+      let mut metadata = generated_metadata(&stack, &binding_name);
+      metadata
+        .labels
+        .get_or_insert_with(BTreeMap::new)
+        .insert(CLUSTER_ROLE_BINDING_LABEL.into(), "true".into());
+
+      let role_binding: RoleBinding = serde_json::from_value(json!({
+          "apiVersion": "rbac.authorization.k8s.io/v1",
+          "kind": "RoleBinding",
+          "metadata": metadata,
+          "subjects": [{
+              "kind": "ServiceAccount",
+              "name": service_account_name,
+              "namespace": namespace
+          }],
+          "roleRef": {
+              "apiGroup": "rbac.authorization.k8s.io",
+              "kind": "ClusterRole",
+              "name": cluster_role
+          }
+      }))
+      .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+
+      self
+        .kubernetes_service
+        .apply(&role_binding)
+        .await
+        .map_err(|err| PulumiStackServiceError::UpdateFailed(err.into()))?;
+    }
+
     Ok(())
   }
+
+  /// Stops the stack job. New runs are suspended and running jobs are deleted,
+  /// which gives pulumi the cancel timeout to stop after SIGTERM.
+  /// Destroys the stack in a one-off job from the stack job template,
+  /// returns whether it succeeded. A failed job stays for inspection and has
+  /// to be deleted to try again.
+  pub(crate) async fn destroy_stack(
+    &self,
+    stack: PulumiStack,
+  ) -> Result<bool, PulumiStackServiceError> {
+    let namespace = stack.metadata.namespace.clone().unwrap();
+    let name =
+      format!("pulumi-{}-destroy", stack.metadata.name.clone().unwrap());
+    let jobs = self
+      .kubernetes_service
+      .all_in_namespace_api::<Job>(&namespace)
+      .await;
+
+    let current = jobs
+      .get_opt(&name)
+      .await
+      .map_err(|err| PulumiStackServiceError::DestroyFailed(err.into()))?;
+    if let Some(status) = current.and_then(|job| job.status) {
+      if status.succeeded.unwrap_or_default() > 0 {
+        return Ok(true);
+      }
+      let failed = status.conditions.iter().flatten().any(|condition| {
+        condition.type_ == "Failed" && condition.status == "True"
+      });
+      if failed {
+        return Err(PulumiStackServiceError::DestroyFailed(
+          format!("job {}/{} failed", namespace, name).into(),
+        ));
+      }
+      return Ok(false);
+    }
+
+    let mut spec = self
+      .render_job(stack.clone())?
+      .spec
+      .and_then(|spec| spec.job_template.spec)
+      .ok_or_else(|| {
+        PulumiStackServiceError::DestroyFailed("job has no template".into())
+      })?;
+    let containers = spec.template.spec.iter_mut().flat_map(|pod| {
+      pod
+        .containers
+        .iter_mut()
+        .filter(|container| container.name == "pulumi")
+    });
+    for container in containers {
+      container.env.get_or_insert_with(Vec::new).push(EnvVar {
+        name: ConfigProvider::DESTROY_VAR.into(),
+        value: Some("true".into()),
+        ..Default::default()
+      });
+    }
+
+    tracing::info!("destroying stack in job {}/{}", namespace, name);
+    let job = Job {
+      metadata: generated_metadata(&stack, &name),
+      spec: Some(spec),
+      ..Default::default()
+    };
+    self
+      .kubernetes_service
+      .apply(&job)
+      .await
+      .map_err(|err| PulumiStackServiceError::DestroyFailed(err.into()))?;
+
+    Ok(false)
+  }
+
   pub(crate) async fn cancel_stack(
     &self,
     stack: PulumiStack,
   ) -> Result<(), PulumiStackServiceError> {
     let namespace = stack.metadata.namespace.unwrap();
-    let name = stack.metadata.name.unwrap();
-    let api = self
+    let name = format!("pulumi-{}", stack.metadata.name.unwrap());
+    let cancel_timeout = self.config_provider.cancel_timeout();
+    let cron_jobs = self
+      .kubernetes_service
+      .all_in_namespace_api::<CronJob>(&namespace)
+      .await;
+    let jobs = self
       .kubernetes_service
-      .all_in_namespace_api::<CronJob>(namespace.clone())
+      .all_in_namespace_api::<Job>(&namespace)
       .await;
 
-    if api.get(&format!("pulumi-{}", &name)).await.is_err() {
+    if cron_jobs
+      .get_opt(&name)
+      .await
+      .map_err(|err| PulumiStackServiceError::CancelFailed(err.into()))?
+      .is_none()
+    {
       return Ok(());
-    };
+    }
 
-    api
-      .delete(
-        &format!("pulumi-{}", &name),
-        &DeleteParams::foreground().grace_period(15),
+    cron_jobs
+      .patch(
+        &name,
+        &PatchParams::default(),
+        &Patch::Merge(json!({ "spec": { "suspend": true } })),
       )
       .await
-      .expect("todo");
+      .map_err(|err| PulumiStackServiceError::CancelFailed(err.into()))?;
 
-    // Poll until the pod is deleted or timeout
-    let mut stream = api
-      .watch(&Default::default(), "0")
+    let running: Vec<String> = jobs
+      .list(&ListParams::default())
       .await
-      .expect("Failed to watch pod")
-      .boxed();
-
-    let timeout_duration = Duration::from_secs(1800);
-    timeout(timeout_duration, async {
-      while let Some(status) =
-        stream.try_next().await.expect("Error while watching")
-      {
-        match status {
-          WatchEvent::Deleted(job)
-            if job.metadata.name
-              == Some(format!("pulumi-{}", &name).clone().to_string()) =>
-          {
-            break;
-          }
-          _ => continue,
-        }
+      .map_err(|err| PulumiStackServiceError::CancelFailed(err.into()))?
+      .into_iter()
+      .filter(|job| {
+        job
+          .metadata
+          .owner_references
+          .iter()
+          .flatten()
+          .any(|owner| owner.kind == "CronJob" && owner.name == name)
+      })
+      .filter_map(|job| job.metadata.name)
+      .collect();
+
+    // pods are terminated with their terminationGracePeriodSeconds, which is
+    // the cancel timeout
+    for job in &running {
+      tracing::info!("cancelling job {}/{}", namespace, job);
+      delete(&jobs, job).await?;
+    }
+    for job in &running {
+      if !wait_deleted(&jobs, job, cancel_timeout + DELETE_TIMEOUT).await? {
+        tracing::warn!("job {}/{} is still terminating", namespace, job);
       }
-    })
-    .await
-    .expect("todo");
+    }
+
+    delete(&cron_jobs, &name).await?;
+    if !wait_deleted(&cron_jobs, &name, DELETE_TIMEOUT).await? {
+      return Err(PulumiStackServiceError::CancelFailed(
+        format!("cron job {}/{} was not deleted", namespace, name).into(),
+      ));
+    }
 
     Ok(())
   }
 }
+
+async fn delete<K>(
+  api: &Api<K>,
+  name: &str,
+) -> Result<(), PulumiStackServiceError>
+where
+  K: Clone + DeserializeOwned + Debug,
+{
+  match api.delete(name, &DeleteParams::foreground()).await {
+    Ok(_) => Ok(()),
+    Err(kube::Error::Api(err)) if err.code == 404 => Ok(()),
+    Err(err) => Err(PulumiStackServiceError::CancelFailed(err.into())),
+  }
+}
+
+/// Polls until the object is gone, returns false on timeout.
+async fn wait_deleted<K>(
+  api: &Api<K>,
+  name: &str,
+  timeout: Duration,
+) -> Result<bool, PulumiStackServiceError>
+where
+  K: Clone + DeserializeOwned + Debug,
+{
+  let deadline = Instant::now() + timeout;
+  while api
+    .get_opt(name)
+    .await
+    .map_err(|err| PulumiStackServiceError::CancelFailed(err.into()))?
+    .is_some()
+  {
+    if Instant::now() >= deadline {
+      return Ok(false);
+    }
+    tokio::time::sleep(DELETE_POLL_INTERVAL).await;
+  }
+
+  Ok(true)
+}
+
+/// Metadata for objects generated for a stack, owned by the stack so they are
+/// garbage collected with it.
+fn generated_metadata(stack: &PulumiStack, name: impl ToString) -> ObjectMeta {
+  ObjectMeta {
+    name: Some(name.to_string()),
+    namespace: stack.metadata.namespace.clone(),
+    labels: Some(BTreeMap::from([
+      (MANAGED_BY_LABEL.to_string(), MANAGED_BY.to_string()),
+      (
+        STACK_LABEL.to_string(),
+        stack_label_value(stack.metadata.name.as_deref().unwrap_or_default()),
+      ),
+    ])),
+    owner_references: stack.controller_owner_ref(&()).map(|owner| vec![owner]),
+    ..Default::default()
+  }
+}
+
+/// Label values are limited to 63 characters, longer stack names are
+/// shortened and suffixed with a hash of the full name.
+fn stack_label_value(name: &str) -> String {
+  const MAX_LENGTH: usize = 63;
+  if name.len() <= MAX_LENGTH {
+    return name.to_string();
+  }
+
+  let hash = hex::encode(&Sha256::digest(name.as_bytes())[..5]);
+  let prefix = name[..MAX_LENGTH - hash.len() - 1]
+    .trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+  format!("{}-{}", prefix, hash)
+}
+
+/// Uid of the stack owning a generated object.
+pub(crate) fn owner_uid(metadata: &ObjectMeta) -> Option<&str> {
+  metadata
+    .owner_references
+    .iter()
+    .flatten()
+    .find(|owner| owner.kind == PulumiStack::kind(&()))
+    .map(|owner| owner.uid.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn short_names_are_kept() {
+    assert_eq!(stack_label_value("my-stack"), "my-stack");
+    let name = "a".repeat(63);
+    assert_eq!(stack_label_value(&name), name);
+  }
+
+  #[test]
+  fn long_names_are_truncated_with_a_hash() {
+    let name = format!("{}-production", "a".repeat(60));
+    let value = stack_label_value(&name);
+    assert_eq!(value.len(), 63);
+    assert!(value.starts_with(&"a".repeat(52)));
+    let hash = hex::encode(&Sha256::digest(name.as_bytes())[..5]);
+    assert!(value.ends_with(&format!("-{}", hash)));
+  }
+
+  #[test]
+  fn truncated_names_sharing_a_prefix_differ() {
+    let prefix = "a".repeat(70);
+    assert_ne!(
+      stack_label_value(&format!("{}-one", prefix)),
+      stack_label_value(&format!("{}-two", prefix))
+    );
+  }
+
+  #[test]
+  fn separators_before_the_hash_are_trimmed() {
+    let name = format!("{}--{}", "a".repeat(50), "b".repeat(20));
+    let value = stack_label_value(&name);
+    assert!(value.len() <= 63);
+    assert!(value.starts_with(&format!("{}-", "a".repeat(50))));
+    assert!(!value.contains("--"));
+  }
+}
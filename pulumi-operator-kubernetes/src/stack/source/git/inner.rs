@@ -5,16 +5,28 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "camelCase")]
 pub struct InnerGitStackSourceSpec {
   pub repository: String,
+  /// Branch, tag or a semver range of tags like `>=1.2.0 <2.0.0` or `1.x`,
+  /// a range resolves to the highest matching tag.
   #[serde(rename = "ref")]
   pub git_ref: Option<String>,
   pub auth: Option<GitAuth>,
+  pub verify: Option<GitVerify>,
+  pub depth: Option<i32>,
+  pub sparse: Option<bool>,
+  pub submodules: Option<bool>,
+  pub lfs: Option<bool>,
+  pub ca_bundle_secret_ref: Option<String>,
+  pub proxy: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct GitAuth {
   pub kind: GitAuthType,
-  pub secret_ref: String,
+  pub secret_ref: Option<String>,
+  pub insecure_skip_host_key_verification: Option<bool>,
+  pub github_app: Option<GitHubAppAuth>,
+  pub token_file: Option<TokenFileAuth>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
@@ -22,4 +34,36 @@ pub struct GitAuth {
 pub enum GitAuthType {
   Basic,
   Ssh,
+  #[serde(rename = "githubApp")]
+  GitHubApp,
+  TokenFile,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GitHubAppAuth {
+  pub app_id: u64,
+  pub installation_id: u64,
+  pub api_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenFileAuth {
+  pub path: String,
+  pub username: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GitVerify {
+  pub mode: GitVerifyMode,
+  pub public_keys_secret_ref: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum GitVerifyMode {
+  Head,
+  Tag,
 }
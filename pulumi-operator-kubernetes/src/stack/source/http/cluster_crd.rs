@@ -0,0 +1,18 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::inner::InnerHttpStackSourceSpec;
+
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[kube(
+  group = "pulumi.stromee.de",
+  version = "v1",
+  kind = "ClusterHttpStackSource",
+  plural = "clusterhttpstacksources"
+)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterHttpStackSourceSpec {
+  #[serde(flatten)]
+  pub inner: InnerHttpStackSourceSpec,
+}
@@ -0,0 +1,19 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::inner::InnerHttpStackSourceSpec;
+
+#[derive(CustomResource, Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[kube(
+  group = "pulumi.stromee.de",
+  version = "v1",
+  kind = "HttpStackSource",
+  plural = "httpstacksources",
+  namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpStackSourceSpec {
+  #[serde(flatten)]
+  pub inner: InnerHttpStackSourceSpec,
+}
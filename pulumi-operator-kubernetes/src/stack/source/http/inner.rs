@@ -0,0 +1,18 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InnerHttpStackSourceSpec {
+  pub url: String,
+  pub sha256: Option<String>,
+  pub headers_secret_ref: Option<String>,
+  pub format: Option<HttpArchiveFormat>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum HttpArchiveFormat {
+  TarGz,
+  Zip,
+}
@@ -0,0 +1,4 @@
+pub mod cluster_crd;
+pub mod crd;
+pub mod inner;
+pub mod repository;
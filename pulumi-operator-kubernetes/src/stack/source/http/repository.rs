@@ -0,0 +1,31 @@
+use crate::Inst;
+use springtime_di::Component;
+
+use crate::kubernetes::service::KubernetesService;
+
+use super::{cluster_crd::ClusterHttpStackSource, crd::HttpStackSource};
+
+#[derive(Component)]
+pub struct HttpStackSourceRepository {
+  kubernetes_service: Inst<KubernetesService>,
+}
+
+impl HttpStackSourceRepository {
+  pub async fn get_namespaced_by_name_and_namespace(
+    &self,
+    name: impl ToString,
+    namespace: impl ToString,
+  ) -> Result<HttpStackSource, kube::Error> {
+    self
+      .kubernetes_service
+      .get_in_namespace(namespace, name)
+      .await
+  }
+
+  pub async fn get_by_name(
+    &self,
+    name: impl ToString,
+  ) -> Result<ClusterHttpStackSource, kube::Error> {
+    self.kubernetes_service.get(name).await
+  }
+}
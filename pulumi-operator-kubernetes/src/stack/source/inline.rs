@@ -0,0 +1,37 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineProgram {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[schemars(schema_with = "free_form_object")]
+  pub variables: Option<serde_json::Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[schemars(schema_with = "free_form_object")]
+  pub resources: Option<serde_json::Value>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[schemars(schema_with = "free_form_object")]
+  pub outputs: Option<serde_json::Value>,
+  pub config_map_ref: Option<InlineConfigMapRef>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InlineConfigMapRef {
+  pub name: String,
+  pub key: Option<String>,
+}
+
+fn free_form_object(_: &mut SchemaGenerator) -> Schema {
+  let mut schema = SchemaObject {
+    instance_type: Some(InstanceType::Object.into()),
+    ..Default::default()
+  };
+  schema
+    .extensions
+    .insert("x-kubernetes-preserve-unknown-fields".into(), true.into());
+  Schema::Object(schema)
+}
@@ -1,10 +1,14 @@
 pub mod git;
+pub mod http;
+pub mod inline;
 pub mod oci;
 
 #[derive(Debug, Clone)]
 pub enum Source {
   Git(git::inner::InnerGitStackSourceSpec),
   Oci(oci::inner::InnerOciStackSourceSpec),
+  Http(http::inner::InnerHttpStackSourceSpec),
+  Inline(inline::InlineProgram),
 }
 
 impl From<git::inner::InnerGitStackSourceSpec> for Source {
@@ -18,3 +22,15 @@ impl From<oci::inner::InnerOciStackSourceSpec> for Source {
     Source::Oci(inner)
   }
 }
+
+impl From<http::inner::InnerHttpStackSourceSpec> for Source {
+  fn from(inner: http::inner::InnerHttpStackSourceSpec) -> Self {
+    Source::Http(inner)
+  }
+}
+
+impl From<inline::InlineProgram> for Source {
+  fn from(inner: inline::InlineProgram) -> Self {
+    Source::Inline(inner)
+  }
+}
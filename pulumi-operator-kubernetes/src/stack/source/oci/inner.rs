@@ -5,4 +5,12 @@ use serde::{Deserialize, Serialize};
 pub struct InnerOciStackSourceSpec {
   pub url: String,
   pub tag: Option<String>,
+  pub digest: Option<String>,
+  pub verify: Option<OciVerify>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct OciVerify {
+  pub public_key_secret_ref: String,
 }
@@ -1,5 +1,73 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{Condition, Time};
+use k8s_openapi::chrono::Utc;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, JsonSchema)]
-pub struct StackStatus {}
+pub const CONDITION_SOURCE_VERIFIED: &str = "SourceVerified";
+pub const CONDITION_DEPENDENCIES_INSTALLED: &str = "DependenciesInstalled";
+pub const CONDITION_INTERRUPTED: &str = "Interrupted";
+
+const MAX_SKIPPED_REVISIONS: usize = 10;
+
+#[derive(
+  Debug, Serialize, Deserialize, PartialEq, Clone, Default, JsonSchema,
+)]
+#[serde(rename_all = "camelCase")]
+pub struct StackStatus {
+  pub conditions: Option<Vec<Condition>>,
+  pub resolved_ref: Option<String>,
+  pub resolved_revision: Option<String>,
+  /// Baseline for watched paths, the last successfully deployed revision.
+  pub last_deployed_revision: Option<String>,
+  /// Generation of the stack the last deployed revision ran with.
+  pub last_deployed_generation: Option<i64>,
+  /// Revisions that were not deployed as none of the watched paths changed.
+  pub skipped_revisions: Option<Vec<String>>,
+}
+
+impl StackStatus {
+  pub fn record_skipped_revision(&mut self, revision: &str) {
+    let skipped = self.skipped_revisions.get_or_insert_with(Vec::new);
+    if skipped.iter().any(|skipped| skipped == revision) {
+      return;
+    }
+    skipped.push(revision.to_string());
+    if skipped.len() > MAX_SKIPPED_REVISIONS {
+      skipped.drain(..skipped.len() - MAX_SKIPPED_REVISIONS);
+    }
+  }
+
+  pub fn set_condition(
+    &mut self,
+    type_: impl ToString,
+    status: bool,
+    reason: impl ToString,
+    message: impl ToString,
+    observed_generation: Option<i64>,
+  ) {
+    let type_ = type_.to_string();
+    let status = if status { "True" } else { "False" }.to_string();
+    let conditions = self.conditions.get_or_insert_with(Vec::new);
+
+    match conditions.iter_mut().find(|c| c.type_ == type_) {
+      Some(condition) => {
+        // keep the transition time when only reason or message changed
+        if condition.status != status {
+          condition.last_transition_time = Time(Utc::now());
+          condition.status = status;
+        }
+        condition.reason = reason.to_string();
+        condition.message = message.to_string();
+        condition.observed_generation = observed_generation;
+      }
+      None => conditions.push(Condition {
+        last_transition_time: Time(Utc::now()),
+        message: message.to_string(),
+        observed_generation,
+        reason: reason.to_string(),
+        status,
+        type_,
+      }),
+    }
+  }
+}
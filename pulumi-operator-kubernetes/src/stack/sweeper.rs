@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::time::Duration;
+
+use k8s_openapi::api::batch::v1::CronJob;
+use k8s_openapi::api::core::v1::ServiceAccount;
+use k8s_openapi::api::rbac::v1::{Role, RoleBinding};
+use k8s_openapi::NamespaceResourceScope;
+use kube::api::DeleteParams;
+use kube::Resource;
+use serde::de::DeserializeOwned;
+use springtime_di::Component;
+
+use crate::config_provider::ConfigProvider;
+use crate::kubernetes::service::KubernetesService;
+use crate::stack::crd::PulumiStack;
+use crate::stack::service::{owner_uid, MANAGED_BY, MANAGED_BY_LABEL};
+use crate::Inst;
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+const JOB_IMAGE: &str = "ghcr.io/stromee/pulumi-operator/";
+
+type StackKey = (String, String);
+
+/// Removes generated objects whose stack is gone. Current objects are
+/// garbage collected through their owner, this catches objects created by
+/// operator versions without owner references.
+#[derive(Component)]
+pub struct OrphanSweeper {
+  kubernetes_service: Inst<KubernetesService>,
+  config_provider: Inst<ConfigProvider>,
+}
+
+impl OrphanSweeper {
+  pub async fn run(&self) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+      interval.tick().await;
+      if let Err(err) = self.sweep().await {
+        tracing::warn!("failed to sweep orphaned resources: {}", err);
+      }
+    }
+  }
+
+  pub async fn sweep(&self) -> Result<(), kube::Error> {
+    // older versions generated a cron job running the job image together with
+    // a Role, RoleBinding and ServiceAccount named after the stack, only the
+    // cron job tells them apart from objects created by someone else
+    let legacy_cron_jobs: HashSet<StackKey> = self
+      .kubernetes_service
+      .all_in_handled_namespaces::<CronJob>()
+      .await?
+      .iter()
+      .filter(|cron_job| is_unowned(*cron_job))
+      .filter_map(legacy_cron_job)
+      .collect();
+    let legacy_roles: HashSet<StackKey> = self
+      .kubernetes_service
+      .all_in_handled_namespaces::<Role>()
+      .await?
+      .into_iter()
+      .filter(is_legacy_role)
+      .filter_map(|role| key(&role))
+      .filter(|key| legacy_cron_jobs.contains(key))
+      .collect();
+    let legacy: HashSet<StackKey> = self
+      .kubernetes_service
+      .all_in_handled_namespaces::<RoleBinding>()
+      .await?
+      .into_iter()
+      .filter(is_legacy_role_binding)
+      .filter_map(|role_binding| key(&role_binding))
+      .filter(|key| legacy_roles.contains(key))
+      .collect();
+    let is_legacy =
+      |key: Option<StackKey>| key.filter(|key| legacy.contains(key));
+
+    self
+      .delete_orphans(|role_binding: &RoleBinding| is_legacy(key(role_binding)))
+      .await?;
+    self
+      .delete_orphans(|role: &Role| is_legacy(key(role)))
+      .await?;
+    self
+      .delete_orphans(|account: &ServiceAccount| is_legacy(key(account)))
+      .await?;
+    // the cron job goes last, it marks the other objects as generated
+    self.delete_orphans(legacy_cron_job).await?;
+
+    Ok(())
+  }
+
+  async fn delete_orphans<K>(
+    &self,
+    legacy_stack: impl Fn(&K) -> Option<StackKey>,
+  ) -> Result<(), kube::Error>
+  where
+    K: Resource<Scope = NamespaceResourceScope>
+      + Clone
+      + DeserializeOwned
+      + Debug,
+    <K as Resource>::DynamicType: Default,
+  {
+    let dynamic_type = K::DynamicType::default();
+    let kind = K::kind(&dynamic_type).into_owned();
+    let delete_legacy = self.config_provider.delete_legacy_resources();
+    let objects = self
+      .kubernetes_service
+      .all_in_handled_namespaces::<K>()
+      .await?;
+    // listed after the objects, so every object of a new stack has its stack
+    let stacks = self
+      .kubernetes_service
+      .all_in_handled_namespaces::<PulumiStack>()
+      .await?;
+    let stack_keys: HashSet<StackKey> = stacks.iter().filter_map(key).collect();
+    let stack_uids: HashSet<&str> = stacks
+      .iter()
+      .filter_map(|stack| stack.metadata.uid.as_deref())
+      .collect();
+
+    for object in objects {
+      let meta = object.meta();
+      let (Some(namespace), Some(name)) = (&meta.namespace, &meta.name) else {
+        continue;
+      };
+      let managed = meta
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.get(MANAGED_BY_LABEL))
+        .is_some_and(|managed_by| managed_by == MANAGED_BY);
+
+      if managed {
+        match owner_uid(meta) {
+          Some(uid) if !stack_uids.contains(uid) => {}
+          _ => continue,
+        }
+        tracing::info!("deleting orphaned {} {}/{}", kind, namespace, name);
+      } else if is_unowned(&object) {
+        let Some(stack) = legacy_stack(&object) else {
+          continue;
+        };
+        if stack_keys.contains(&stack) {
+          continue;
+        }
+        if !delete_legacy {
+          tracing::info!(
+            "found orphaned {} {}/{} of stack {} from an older operator \
+             version, set deleteLegacyResources to delete it",
+            kind,
+            namespace,
+            name,
+            stack.1
+          );
+          continue;
+        }
+        tracing::info!(
+          "deleting orphaned {} {}/{} of stack {}",
+          kind,
+          namespace,
+          name,
+          stack.1
+        );
+      } else {
+        continue;
+      }
+
+      match self
+        .kubernetes_service
+        .all_in_namespace_api::<K>(namespace)
+        .await
+        .delete(name, &DeleteParams::default())
+        .await
+      {
+        Ok(_) => {}
+        Err(kube::Error::Api(err)) if err.code == 404 => {}
+        Err(err) => return Err(err),
+      }
+    }
+
+    Ok(())
+  }
+}
+
+fn key<K: Resource>(object: &K) -> Option<StackKey> {
+  Some((
+    object.meta().namespace.clone()?,
+    object.meta().name.clone()?,
+  ))
+}
+
+fn is_unowned<K: Resource>(object: &K) -> bool {
+  object
+    .meta()
+    .owner_references
+    .as_ref()
+    .is_none_or(Vec::is_empty)
+}
+
+fn is_legacy_role(role: &Role) -> bool {
+  let wildcard = Some(vec!["*".to_string()]);
+  is_unowned(role)
+    && matches!(role.rules.as_deref(), Some([rule])
+    if rule.api_groups == wildcard
+      && rule.resources == wildcard
+      && rule.verbs == vec!["*".to_string()])
+}
+
+fn is_legacy_role_binding(role_binding: &RoleBinding) -> bool {
+  let name = role_binding.metadata.name.as_deref();
+  is_unowned(role_binding)
+    && role_binding.role_ref.kind == "Role"
+    && Some(role_binding.role_ref.name.as_str()) == name
+    && matches!(role_binding.subjects.as_deref(), Some([subject])
+      if subject.kind == "ServiceAccount" && Some(subject.name.as_str()) == name)
+}
+
+// cron jobs were named pulumi-<stack> and ran the job image
+fn legacy_cron_job(cron_job: &CronJob) -> Option<StackKey> {
+  let (namespace, name) = key(cron_job)?;
+  let stack = name.strip_prefix("pulumi-")?;
+  let runs_job = cron_job
+    .spec
+    .as_ref()?
+    .job_template
+    .spec
+    .as_ref()?
+    .template
+    .spec
+    .as_ref()?
+    .containers
+    .iter()
+    .any(|container| {
+      container
+        .image
+        .as_deref()
+        .is_some_and(|image| image.starts_with(JOB_IMAGE))
+    });
+
+  runs_job.then(|| (namespace, stack.to_string()))
+}